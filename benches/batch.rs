@@ -0,0 +1,102 @@
+use bruss_data::{Batch, Segment};
+use chrono::{FixedOffset, NaiveDate};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion, Throughput};
+use serde_json::{json, Value};
+use tt::{TTRoute, TTStop, TTTrip};
+
+const STOPS: u16 = 2000;
+const ROUTES: u16 = 100;
+const PATHS_PER_ROUTE: u16 = 6;
+
+/// Synthetic dump, in the format of the TT api: each route has a few path variants, each one
+/// followed by many trips along the day.
+fn dataset(trips: usize) -> (Vec<TTStop>, Vec<TTRoute>, Vec<TTTrip>) {
+    let stops = (1..=STOPS)
+        .map(|id| serde_json::from_value(json!({
+            "stopId": id,
+            "stopCode": format!("{}z", id),
+            "stopDesc": "",
+            "stopLat": 46.0 + id as f64 * 1e-4,
+            "stopLon": 11.0 + id as f64 * 1e-4,
+            "stopLevel": 200,
+            "stopName": format!("Stop {}", id),
+            "street": null,
+            "town": "Trento",
+            "type": "U",
+            "wheelchairBoarding": 1,
+        })).unwrap())
+        .collect();
+    let routes = (1..=ROUTES)
+        .map(|id| serde_json::from_value(json!({
+            "routeId": id,
+            "routeType": 3,
+            "areaId": 23,
+            "routeColor": "ff0000",
+            "routeShortName": id.to_string(),
+            "routeLongName": format!("Route {}", id),
+            "type": "U",
+        })).unwrap())
+        .collect();
+    let trips = (0..trips)
+        .map(|i| {
+            let route = (i as u16 % ROUTES) + 1;
+            let variant = (i as u16 / ROUTES) % PATHS_PER_ROUTE;
+            let len = 20 + variant * 2;
+            let first = (route * 17 + variant) % (STOPS - len);
+            let start = 5 * 3600 + (i % 900) as u32 * 60;
+            let stop_times = (0..len)
+                .map(|s| {
+                    let t = start + s as u32 * 90;
+                    let t = format!("{:02}:{:02}:{:02}", t / 3600, t / 60 % 60, t % 60);
+                    json!({
+                        "arrivalTime": t,
+                        "departureTime": t,
+                        "stopId": first + s + 1,
+                        "stopSequence": s + 1,
+                        "tripId": format!("trip{}", i),
+                        "type": "U",
+                    })
+                })
+                .collect::<Vec<Value>>();
+            serde_json::from_value(json!({
+                "tripId": format!("trip{}", i),
+                "delay": null,
+                "directionId": variant % 2,
+                "stopNext": 0,
+                "stopLast": 0,
+                "matricolaBus": null,
+                "routeId": route,
+                "stopTimes": stop_times,
+                "type": "U",
+                "tripHeadsign": format!("Headsign {}", route),
+                "lastEventRecivedAt": null,
+            })).unwrap()
+        })
+        .collect();
+    (stops, routes, trips)
+}
+
+fn batch(c: &mut Criterion) {
+    let day = NaiveDate::from_ymd_opt(2024, 5, 6).unwrap();
+    let offset = FixedOffset::east_opt(2 * 3600).unwrap();
+    let segments: Vec<Segment> = Vec::new();
+    let mut group = c.benchmark_group("batch");
+    group.sample_size(10);
+    for size in [1_000, 10_000, 40_000] {
+        group.throughput(Throughput::Elements(size as u64));
+        group.bench_with_input(BenchmarkId::from_parameter(size), &size, |b, &size| {
+            b.iter_batched(
+                || dataset(size),
+                |(stops, routes, trips)| Batch::new()
+                    .segments(&segments)
+                    .service_day(day, &offset)
+                    .run(stops, routes, trips),
+                criterion::BatchSize::LargeInput,
+            )
+        });
+    }
+    group.finish();
+}
+
+criterion_group!(benches, batch);
+criterion_main!(benches);
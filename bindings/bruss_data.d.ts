@@ -0,0 +1,129 @@
+// This file is generated by bruss_data: do not edit it by hand.
+// Regenerate it with `BRUSS_UPDATE_BINDINGS=1 cargo test --features schema`.
+
+export type AreaType = "E" | "U";
+
+export type TimeDelta = [number, number];
+
+export type Coords = [number, number];
+
+export type Direction = "f" | "b";
+
+export type RoutingType = "bus" | "railway" | "cableway";
+
+export type SegmentSource = "routed" | "fallback" | "manual";
+
+export interface StopTime {
+    arrival: TimeDelta;
+    departure: TimeDelta;
+}
+
+export type StopTimes = { [key: string]: StopTime };
+
+export interface Area {
+    id: number;
+    label: string;
+    type: AreaType;
+}
+
+export interface Stop {
+    id: number;
+    code: string;
+    description: string;
+    position: Coords;
+    altitude: number;
+    name: string;
+    street: string | null;
+    town: string | null;
+    type: AreaType;
+    wheelchair_boarding: boolean;
+    station?: string;
+}
+
+export interface StopKey {
+    type: AreaType;
+    id: number;
+}
+
+export interface Route {
+    id: number;
+    type: number;
+    area: number;
+    area_ty: AreaType;
+    color: string;
+    name: string;
+    code: string;
+}
+
+export interface Trip {
+    id: string;
+    direction: Direction;
+    next_stop?: number;
+    last_stop?: number;
+    bus_id: number | null;
+    route: number;
+    headsign: string;
+    path: string;
+    times: StopTimes;
+    type: AreaType;
+    last_event: string | null;
+}
+
+export interface Path {
+    id: string;
+    type: AreaType;
+    sequence: number[];
+    rty?: RoutingType;
+}
+
+export interface Segment {
+    from: number;
+    to: number;
+    type: AreaType;
+    geometry: Coords[];
+    distance?: number;
+    duration?: number;
+    source?: SegmentSource;
+}
+
+export interface ScheduleHints {
+    route: number;
+    type: AreaType;
+    times: StopTimes;
+    direction: Direction;
+}
+
+export interface Schedule {
+    id: string;
+    departure: { $date: { $numberLong: string } };
+    arrival: { $date: { $numberLong: string } };
+    hints: ScheduleHints;
+}
+
+export interface Transfer {
+    from: StopKey;
+    to: StopKey;
+    distance: number;
+    duration: TimeDelta;
+}
+
+export interface Station {
+    id: string;
+    name: string;
+    position: Coords;
+    stops: StopKey[];
+}
+
+export interface ServedRoute {
+    route: number;
+    direction: Direction;
+    headsigns: string[];
+    trips: number;
+    terminus: boolean;
+}
+
+export interface StopRoutes {
+    id: number;
+    type: AreaType;
+    routes: ServedRoute[];
+}
@@ -0,0 +1,184 @@
+use std::collections::{HashMap, HashSet};
+
+use chrono::{DateTime, NaiveDate, NaiveTime, Offset, TimeDelta, TimeZone, Utc};
+#[cfg(feature = "parallel")]
+use rayon::prelude::*;
+use tt::{AreaType, TTRoute, TTStop, TTTrip};
+
+use crate::{AreaHelper, FromTT, Path, PathRegistry, Route, Schedule, Segment, Stop, StopPair, Trip, Type};
+
+/// A record of the dump that couldn't be converted.
+#[derive(Debug)]
+pub struct RecordError {
+    pub ty: Type,
+    pub id: String,
+    pub error: String,
+}
+
+/// # Batch
+/// Converts a whole TT dump in one pass.
+///
+/// Stops, routes and trips are converted in parallel when the `parallel` feature is enabled, then
+/// paths are deduplicated through a `PathRegistry`. Records that can't be converted are collected
+/// in `BatchOutput::errors`, without aborting the whole batch. The trips of a route with an
+/// unrecognized type are skipped, and the route is reported once.
+#[derive(Default)]
+pub struct Batch<'a> {
+    segments: &'a [Segment],
+    /// Local midnight starting the service day.
+    service_day: Option<DateTime<Utc>>,
+}
+
+pub struct BatchOutput {
+    pub stops: AreaHelper<Stop>,
+    pub routes: HashMap<u16, Route>,
+    pub trips: Vec<Trip>,
+    /// Empty unless a service day has been set.
+    pub schedules: Vec<Schedule>,
+    pub paths: PathRegistry,
+    /// Stop pairs of the paths that don't have a segment yet.
+    pub missing_segments: Vec<(AreaType, StopPair)>,
+    pub errors: Vec<RecordError>,
+}
+
+impl<'a> Batch<'a> {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Segments already known, used to compute `BatchOutput::missing_segments`.
+    pub fn segments(mut self, segments: &'a [Segment]) -> Self {
+        self.segments = segments;
+        self
+    }
+
+    /// Day the trips are scheduled in: if set, a `Schedule` is produced for each trip.
+    ///
+    /// TT times are local times of `tz` (e.g. `chrono_tz::Europe::Rome`) counted from the midnight
+    /// starting `date`. The offset is the one in effect at noon, so on the days the clocks change
+    /// the trips before the change are shifted by an hour.
+    pub fn service_day<Tz: TimeZone>(mut self, date: NaiveDate, tz: &Tz) -> Self {
+        let midnight = date.and_time(NaiveTime::MIN);
+        let offset = tz.offset_from_utc_datetime(&date.and_hms_opt(12, 0, 0).unwrap()).fix();
+        self.service_day = Some(Utc.from_utc_datetime(&(midnight - TimeDelta::seconds(offset.local_minus_utc() as i64))));
+        self
+    }
+
+    pub fn run(self, stops: Vec<TTStop>, routes: Vec<TTRoute>, trips: Vec<TTTrip>) -> BatchOutput {
+        let stops = map(stops, Stop::from_tt).into_iter().collect::<AreaHelper<Stop>>();
+        let routes = map(routes, Route::from_tt).into_iter()
+            .map(|r| (r.id, r))
+            .collect::<HashMap<_, _>>();
+
+        let mut errors = Vec::new();
+        let converted = map(trips, |t| {
+            let id = t.id.clone();
+            Trip::try_from_tt_with_sequence(t).map_err(|e| RecordError { ty: Type::Trip, id, error: e.to_string() })
+        });
+
+        let mut paths = PathRegistry::new();
+        // routes with an unrecognized type, reported once
+        let mut unrecognized = HashSet::new();
+        let mut output = Vec::with_capacity(converted.len());
+        for c in converted {
+            let (trip, dep, sequence) = match c {
+                Ok(v) => v,
+                Err(e) => { errors.push(e); continue }
+            };
+            let rty = match routes.get(&trip.route).map(Route::try_routing_type) {
+                Some(Some(r)) => r,
+                Some(None) => {
+                    if unrecognized.insert(trip.route) {
+                        errors.push(RecordError { ty: Type::Route, id: trip.route.to_string(), error: format!("unrecognized route type {}", routes[&trip.route].ty) });
+                    }
+                    continue
+                }
+                None => {
+                    errors.push(RecordError { ty: Type::Trip, id: trip.id, error: format!("unknown route {}", trip.route) });
+                    continue
+                }
+            };
+            if let Err(e) = paths.register(&trip, Path::new(sequence, trip.ty, rty)) {
+                errors.push(RecordError { ty: Type::Path, id: e.id.clone(), error: e.to_string() });
+                continue
+            }
+            output.push((trip, dep));
+        }
+
+        let schedules = match self.service_day {
+            Some(day) => map_ref(&output, |(t, dep): &(Trip, TimeDelta)| Schedule::from_trip(t, day + *dep))
+                .into_iter()
+                .flatten()
+                .collect(),
+            None => Vec::new(),
+        };
+        let missing_segments = paths.missing_segments(self.segments);
+
+        BatchOutput {
+            stops,
+            routes,
+            trips: output.into_iter().map(|(t, _)| t).collect(),
+            schedules,
+            paths,
+            missing_segments,
+            errors,
+        }
+    }
+}
+
+fn map<T: Send, U: Send>(v: Vec<T>, f: impl Fn(T) -> U + Sync + Send) -> Vec<U> {
+    #[cfg(feature = "parallel")]
+    { v.into_par_iter().map(f).collect() }
+    #[cfg(not(feature = "parallel"))]
+    { v.into_iter().map(f).collect() }
+}
+
+fn map_ref<T: Sync, U: Send>(v: &[T], f: impl Fn(&T) -> U + Sync + Send) -> Vec<U> {
+    #[cfg(feature = "parallel")]
+    { v.par_iter().map(f).collect() }
+    #[cfg(not(feature = "parallel"))]
+    { v.iter().map(f).collect() }
+}
+
+#[test]
+fn batch_test_run() {
+    use chrono::FixedOffset;
+    use serde_json::json;
+    use crate::test_util::tt_trip;
+
+    let stops = (1..=4)
+        .map(|id| serde_json::from_value(json!({
+            "stopId": id, "stopCode": "", "stopDesc": "", "stopLat": 46.0, "stopLon": 11.0, "stopLevel": 200,
+            "stopName": "", "street": null, "town": null, "type": "U", "wheelchairBoarding": 1,
+        })).unwrap())
+        .collect();
+    let route = |id, ty| serde_json::from_value(json!({
+        "routeId": id, "routeType": ty, "areaId": 23, "routeColor": "", "routeShortName": "", "routeLongName": "", "type": "U",
+    })).unwrap();
+    let trips = vec![
+        tt_trip("a", 5, &[1, 2, 3], "08:00"),
+        tt_trip("b", 5, &[1, 2, 3], "09:00"),
+        tt_trip("c", 5, &[3, 4], "10:00"),
+        // route with an unknown type: one error for both trips
+        tt_trip("d", 6, &[1, 2], "08:00"),
+        tt_trip("e", 6, &[1, 2], "09:00"),
+        tt_trip("f", 7, &[1, 2], "08:00"),
+        tt_trip("g", 5, &[], "08:00"),
+    ];
+    let segments = [Segment::new(1, 2, AreaType::U, Vec::new())];
+    let o = Batch::new()
+        .segments(&segments)
+        .service_day(NaiveDate::from_ymd_opt(2024, 5, 6).unwrap(), &FixedOffset::east_opt(2 * 3600).unwrap())
+        .run(stops, vec![route(5, 3), route(6, 9)], trips);
+
+    assert_eq!(o.stops.values().count(), 4);
+    assert_eq!(o.trips.len(), 3);
+    assert_eq!(o.paths.len(), 2);
+    assert_eq!(o.paths.trip_count(&o.trips[0].path), 2);
+    assert_eq!(o.schedules.len(), 3);
+    // 09:00 in summer time
+    assert_eq!(o.schedules[1].departure, Utc.with_ymd_and_hms(2024, 5, 6, 7, 0, 0).unwrap());
+    assert_eq!(o.missing_segments, vec![(AreaType::U, (2, 3)), (AreaType::U, (3, 4))]);
+    let errors = o.errors.iter().map(|e| (e.ty.collection(), e.id.as_str())).collect::<Vec<_>>();
+    assert_eq!(errors, vec![("routes", "6"), ("trips", "f"), ("trips", "g")]);
+}
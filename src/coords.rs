@@ -11,6 +11,8 @@ pub struct Coords {
 
 impl Coords {
     const DEG_METER: f64 = 113000.44;
+    /// Mean earth radius, in meters.
+    pub const EARTH_RADIUS: f64 = 6_371_008.8;
 
     pub fn new(lat: f64, lng: f64) -> Self {
         Self { lat, lng }
@@ -19,6 +21,42 @@ impl Coords {
     pub fn to_osrm_query(&self) -> String {
         format!("{},{}", self.lng, self.lat)
     }
+
+    /// Great-circle distance in meters from `other`.
+    /// Unlike `Sub`, it's accurate at any distance.
+    pub fn haversine(&self, other: &Self) -> f64 {
+        Self::EARTH_RADIUS * self.central_angle(other)
+    }
+
+    /// Angle (in radians) between the two positions, seen from the center of the earth.
+    fn central_angle(&self, other: &Self) -> f64 {
+        let (lat1, lat2) = (self.lat.to_radians(), other.lat.to_radians());
+        let dlat = lat2 - lat1;
+        let dlng = (other.lng - self.lng).to_radians();
+        let a = (dlat / 2.).sin().powi(2) + lat1.cos() * lat2.cos() * (dlng / 2.).sin().powi(2);
+        2. * a.sqrt().min(1.).asin()
+    }
+
+    /// Point at fraction `f` (from 0 to 1) of the straight line (in degrees) to `other`.
+    pub fn lerp(&self, other: &Self, f: f64) -> Self {
+        Self::new(self.lat + (other.lat - self.lat) * f, self.lng + (other.lng - self.lng) * f)
+    }
+
+    /// Point at fraction `f` (from 0 to 1) of the great circle arc to `other`.
+    pub fn slerp(&self, other: &Self, f: f64) -> Self {
+        let d = self.central_angle(other);
+        if d < 1e-12 {
+            return self.clone();
+        }
+        let (lat1, lng1) = (self.lat.to_radians(), self.lng.to_radians());
+        let (lat2, lng2) = (other.lat.to_radians(), other.lng.to_radians());
+        let a = ((1. - f) * d).sin() / d.sin();
+        let b = (f * d).sin() / d.sin();
+        let x = a * lat1.cos() * lng1.cos() + b * lat2.cos() * lng2.cos();
+        let y = a * lat1.cos() * lng1.sin() + b * lat2.cos() * lng2.sin();
+        let z = a * lat1.sin() + b * lat2.sin();
+        Self::new(z.atan2((x * x + y * y).sqrt()).to_degrees(), y.atan2(x).to_degrees())
+    }
 }
 
 impl Sub for &Coords {
@@ -0,0 +1,292 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Utc};
+use serde::Serialize;
+use tt::AreaType;
+
+use crate::{Area, AreaHelper, BatchOutput, Path, Route, Schedule, Segment, Stop, StopKey, Trip};
+
+/// A broken reference between the records of a `Dataset`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "violation", rename_all = "snake_case")]
+pub enum Violation {
+    /// The route of a trip doesn't exist.
+    UnknownRoute { trip: String, route: u16 },
+    /// The path of a trip doesn't exist.
+    UnknownPath { trip: String, path: String },
+    /// A trip and its path belong to different area types.
+    AreaMismatch { trip: String, path: String },
+    /// A stop of a path doesn't exist in the area type of the path.
+    UnknownStop { path: String, stop: StopKey },
+    /// Two consecutive stops of a path aren't joined by a segment.
+    MissingSegment { path: String, from: StopKey, to: StopKey },
+    /// The area of a route doesn't exist.
+    UnknownArea { route: u16, area: u16 },
+    /// The trip of a schedule doesn't exist.
+    UnknownTrip { schedule: String, departure: DateTime<Utc> },
+}
+
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct IntegrityReport {
+    pub violations: Vec<Violation>,
+}
+
+impl IntegrityReport {
+    pub fn is_ok(&self) -> bool {
+        self.violations.is_empty()
+    }
+}
+
+/// # Dataset
+/// All the data of a network in memory, with indexes to go from a record to the ones it
+/// references and back.
+///
+/// Records are never checked when they are added: use `check` to find the broken references.
+#[derive(Default)]
+pub struct Dataset {
+    stops: AreaHelper<Stop>,
+    routes: HashMap<u16, Route>,
+    areas: Vec<Area>,
+    paths: HashMap<String, Path>,
+    segments: HashMap<(StopKey, StopKey), Segment>,
+    trips: HashMap<String, Trip>,
+    schedules: Vec<Schedule>,
+    trips_by_route: HashMap<u16, Vec<String>>,
+    trips_by_path: HashMap<String, Vec<String>>,
+    schedules_by_trip: HashMap<String, Vec<usize>>,
+}
+
+impl Dataset {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn add_stops(&mut self, stops: impl IntoIterator<Item = Stop>) {
+        for s in stops {
+            self.stops.insert(s);
+        }
+    }
+
+    pub fn add_routes(&mut self, routes: impl IntoIterator<Item = Route>) {
+        self.routes.extend(routes.into_iter().map(|r| (r.id, r)));
+    }
+
+    pub fn add_areas(&mut self, areas: impl IntoIterator<Item = Area>) {
+        self.areas.extend(areas);
+    }
+
+    pub fn add_paths(&mut self, paths: impl IntoIterator<Item = Path>) {
+        self.paths.extend(paths.into_iter().map(|p| (p.id.clone(), p)));
+    }
+
+    pub fn add_segments(&mut self, segments: impl IntoIterator<Item = Segment>) {
+        self.segments.extend(segments.into_iter().map(|s| (segment_key(s.ty, s.from, s.to), s)));
+    }
+
+    /// Adds trips, replacing the ones with the same id.
+    pub fn add_trips(&mut self, trips: impl IntoIterator<Item = Trip>) {
+        for t in trips {
+            if let Some(old) = self.trips.remove(&t.id) {
+                self.unindex_trip(&old);
+            }
+            self.trips_by_route.entry(t.route).or_default().push(t.id.clone());
+            self.trips_by_path.entry(t.path.clone()).or_default().push(t.id.clone());
+            self.trips.insert(t.id.clone(), t);
+        }
+    }
+
+    fn unindex_trip(&mut self, trip: &Trip) {
+        if let Some(v) = self.trips_by_route.get_mut(&trip.route) {
+            v.retain(|id| *id != trip.id);
+        }
+        if let Some(v) = self.trips_by_path.get_mut(&trip.path) {
+            v.retain(|id| *id != trip.id);
+        }
+    }
+
+    pub fn add_schedules(&mut self, schedules: impl IntoIterator<Item = Schedule>) {
+        for s in schedules {
+            self.schedules_by_trip.entry(s.id.clone()).or_default().push(self.schedules.len());
+            self.schedules.push(s);
+        }
+    }
+
+    pub fn stops(&self) -> &AreaHelper<Stop> {
+        &self.stops
+    }
+
+    pub fn stop(&self, key: &StopKey) -> Option<&Stop> {
+        self.stops.get_key(key)
+    }
+
+    pub fn routes(&self) -> impl Iterator<Item = &Route> {
+        self.routes.values()
+    }
+
+    pub fn route(&self, id: u16) -> Option<&Route> {
+        self.routes.get(&id)
+    }
+
+    pub fn areas(&self) -> &[Area] {
+        &self.areas
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.values()
+    }
+
+    pub fn path(&self, id: &str) -> Option<&Path> {
+        self.paths.get(id)
+    }
+
+    pub fn segments(&self) -> impl Iterator<Item = &Segment> {
+        self.segments.values()
+    }
+
+    pub fn segment(&self, ty: AreaType, from: u16, to: u16) -> Option<&Segment> {
+        self.segments.get(&segment_key(ty, from, to))
+    }
+
+    pub fn trips(&self) -> impl Iterator<Item = &Trip> {
+        self.trips.values()
+    }
+
+    pub fn trip(&self, id: &str) -> Option<&Trip> {
+        self.trips.get(id)
+    }
+
+    pub fn schedules(&self) -> &[Schedule] {
+        &self.schedules
+    }
+
+    pub fn route_of(&self, trip: &Trip) -> Option<&Route> {
+        self.routes.get(&trip.route)
+    }
+
+    pub fn path_of(&self, trip: &Trip) -> Option<&Path> {
+        self.paths.get(&trip.path)
+    }
+
+    pub fn trips_of_route(&self, route: u16) -> impl Iterator<Item = &Trip> {
+        self.trips_by_route.get(&route).into_iter().flatten().filter_map(|id| self.trips.get(id))
+    }
+
+    pub fn trips_of_path(&self, path: &str) -> impl Iterator<Item = &Trip> {
+        self.trips_by_path.get(path).into_iter().flatten().filter_map(|id| self.trips.get(id))
+    }
+
+    pub fn schedules_of_trip(&self, trip: &str) -> impl Iterator<Item = &Schedule> {
+        self.schedules_by_trip.get(trip).into_iter().flatten().map(|i| &self.schedules[*i])
+    }
+
+    /// Stops of a path, in order. Missing stops are `None`.
+    pub fn stops_of_path<'a>(&'a self, path: &'a Path) -> impl Iterator<Item = Option<&'a Stop>> {
+        path.sequence.iter().map(|s| self.stops.get(path.ty).get(s))
+    }
+
+    /// Checks all the references between records. Violations are sorted by the kind of record
+    /// they're found in (routes, trips, paths, schedules), then by id (and departure, for
+    /// schedules).
+    pub fn check(&self) -> IntegrityReport {
+        let mut violations = Vec::new();
+
+        let mut routes = self.routes.values().collect::<Vec<_>>();
+        routes.sort_by_key(|r| r.id);
+        for r in routes {
+            if !self.areas.iter().any(|a| a.id == r.area && a.ty == r.area_ty) {
+                violations.push(Violation::UnknownArea { route: r.id, area: r.area });
+            }
+        }
+
+        let mut trips = self.trips.values().collect::<Vec<_>>();
+        trips.sort_by(|a, b| a.id.cmp(&b.id));
+        for t in trips {
+            if !self.routes.contains_key(&t.route) {
+                violations.push(Violation::UnknownRoute { trip: t.id.clone(), route: t.route });
+            }
+            match self.paths.get(&t.path) {
+                None => violations.push(Violation::UnknownPath { trip: t.id.clone(), path: t.path.clone() }),
+                Some(p) if p.ty != t.ty => violations.push(Violation::AreaMismatch { trip: t.id.clone(), path: p.id.clone() }),
+                _ => {}
+            }
+        }
+
+        let mut paths = self.paths.values().collect::<Vec<_>>();
+        paths.sort_by(|a, b| a.id.cmp(&b.id));
+        for p in paths {
+            for (s, stop) in p.sequence.iter().zip(self.stops_of_path(p)) {
+                if stop.is_none() {
+                    violations.push(Violation::UnknownStop { path: p.id.clone(), stop: StopKey::new(p.ty, *s) });
+                }
+            }
+            for (from, to) in p.segments() {
+                if !self.segments.contains_key(&segment_key(p.ty, from, to)) {
+                    violations.push(Violation::MissingSegment { path: p.id.clone(), from: StopKey::new(p.ty, from), to: StopKey::new(p.ty, to) });
+                }
+            }
+        }
+
+        let mut schedules = self.schedules.iter().collect::<Vec<_>>();
+        schedules.sort_by(|a, b| a.id.cmp(&b.id).then_with(|| a.departure.cmp(&b.departure)));
+        for s in schedules {
+            if !self.trips.contains_key(&s.id) {
+                violations.push(Violation::UnknownTrip { schedule: s.id.clone(), departure: s.departure });
+            }
+        }
+
+        IntegrityReport { violations }
+    }
+}
+
+fn segment_key(ty: AreaType, from: u16, to: u16) -> (StopKey, StopKey) {
+    (StopKey::new(ty, from), StopKey::new(ty, to))
+}
+
+impl From<BatchOutput> for Dataset {
+    fn from(value: BatchOutput) -> Self {
+        let BatchOutput { stops, routes, trips, schedules, paths, .. } = value;
+        let mut o = Self { stops, routes, ..Default::default() };
+        o.add_paths(paths.into_paths());
+        o.add_trips(trips);
+        o.add_schedules(schedules);
+        o
+    }
+}
+
+#[test]
+fn dataset_test_check() {
+    use chrono::TimeZone;
+    use crate::{test_util::{stop, trip}, Coords, RoutingType};
+
+    let stop = |id| stop(AreaType::U, id, "", Coords::new(46.07, 11.12));
+    let path = Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus);
+    let mut nowhere = trip("b", 6, &path);
+    nowhere.path = "nowhere".to_string();
+    // schedules of unknown trips
+    let (y, z) = (trip("y", 5, &path), trip("z", 5, &path));
+
+    let mut d = Dataset::new();
+    d.add_stops([stop(1), stop(3)]);
+    d.add_areas([Area::new(1, String::new(), AreaType::U)]);
+    d.add_routes([Route::new(5, 3, 1, AreaType::U, String::new(), String::new(), String::new())]);
+    d.add_segments([Segment::new(1, 2, AreaType::U, Vec::new())]);
+    d.add_trips([trip("a", 5, &path), nowhere]);
+    d.add_paths([path]);
+    let at = |h| Utc.with_ymd_and_hms(2024, 5, 1, h, 0, 0).unwrap();
+    d.add_schedules([Schedule::from_trip(&z, at(9)).unwrap(), Schedule::from_trip(&y, at(8)).unwrap(), Schedule::from_trip(&z, at(7)).unwrap()]);
+
+    assert_eq!(d.trips_of_route(5).count(), 1);
+    assert_eq!(d.trips_of_path(&d.trip("a").unwrap().path).count(), 1);
+
+    let path = d.trip("a").unwrap().path.clone();
+    let k = |id| StopKey::new(AreaType::U, id);
+    assert_eq!(d.check().violations, vec![
+        Violation::UnknownRoute { trip: "b".to_string(), route: 6 },
+        Violation::UnknownPath { trip: "b".to_string(), path: "nowhere".to_string() },
+        Violation::UnknownStop { path: path.clone(), stop: k(2) },
+        Violation::MissingSegment { path, from: k(2), to: k(3) },
+        Violation::UnknownTrip { schedule: "y".to_string(), departure: at(8) },
+        Violation::UnknownTrip { schedule: "z".to_string(), departure: at(7) },
+        Violation::UnknownTrip { schedule: "z".to_string(), departure: at(9) },
+    ]);
+}
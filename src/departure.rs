@@ -0,0 +1,259 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::{Direction, Route, Schedule, Station, StopKey, Trip};
+
+/// Realtime state of a departure.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum DepartureStatus {
+    /// There is no realtime data about the trip.
+    Scheduled,
+    OnTime,
+    Delayed,
+    Early,
+    /// The vehicle has already left the stop.
+    Departed,
+}
+
+/// A row of a departure board.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Departure {
+    pub stop: StopKey,
+    pub trip: String,
+    pub route: u16,
+    /// Name, code and color of the route, empty if the route is unknown.
+    pub name: String,
+    pub code: String,
+    pub color: String,
+    /// Empty if the trip is unknown.
+    pub headsign: String,
+    pub direction: Direction,
+    pub scheduled: DateTime<Utc>,
+    /// `scheduled` plus the delay of the trip; the same as `scheduled` without realtime data.
+    pub expected: DateTime<Utc>,
+    pub status: DepartureStatus,
+}
+
+/// # DepartureQuery
+/// The next departures from a stop, or from all the stops of a station, after a point in time.
+///
+/// It joins the schedules serving the stops with their `Route` and their `Trip`, whose realtime
+/// fields give the expected time (`delay`, in minutes) and tell if the vehicle has already left
+/// the stop (`last_stop`). A trip without a `last_event` has no realtime data.
+///
+/// Trips ending at the stop aren't departures, so they're left out. Departures are sorted by
+/// scheduled time.
+#[derive(Debug, Clone)]
+pub struct DepartureQuery {
+    stops: Vec<StopKey>,
+    after: DateTime<Utc>,
+    limit: usize,
+}
+
+impl DepartureQuery {
+    pub const DEFAULT_LIMIT: usize = 10;
+
+    pub fn new(stop: StopKey, after: DateTime<Utc>) -> Self {
+        Self { stops: vec![stop], after, limit: Self::DEFAULT_LIMIT }
+    }
+
+    /// Departures from all the stops of `station`.
+    pub fn station(station: &Station, after: DateTime<Utc>) -> Self {
+        Self { stops: station.stops.clone(), after, limit: Self::DEFAULT_LIMIT }
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+
+    /// Runs the query on in-memory data: `route` and `trip` look up the route and the (live) trip
+    /// of a schedule.
+    pub fn run<'a>(
+        &self,
+        schedules: impl IntoIterator<Item = &'a Schedule>,
+        route: impl Fn(u16) -> Option<&'a Route>,
+        trip: impl Fn(&str) -> Option<&'a Trip>,
+    ) -> Vec<Departure> {
+        let mut o = schedules.into_iter()
+            .flat_map(|s| self.stops.iter().map(move |k| (k, s)))
+            .filter_map(|(k, s)| departure(k, s, route(s.hints.route), trip(&s.id)))
+            .filter(|d| d.scheduled >= self.after)
+            .collect::<Vec<_>>();
+        self.finish(&mut o);
+        o
+    }
+
+    fn finish(&self, departures: &mut Vec<Departure>) {
+        departures.sort_by(|a, b| a.scheduled.cmp(&b.scheduled).then_with(|| a.trip.cmp(&b.trip)));
+        departures.truncate(self.limit);
+    }
+}
+
+/// The departure of schedule `s` from `stop`, if it stops there and doesn't end there.
+fn departure(stop: &StopKey, s: &Schedule, route: Option<&Route>, trip: Option<&Trip>) -> Option<Departure> {
+    if s.hints.ty != stop.ty {
+        return None;
+    }
+    let at = s.hints.times.get(&stop.id)?.departure;
+    if s.hints.times.iter().all(|(id, t)| *id == stop.id || t.departure <= at) {
+        return None;
+    }
+    let scheduled = s.departure + at;
+    let live = trip.filter(|t| t.last_event.is_some());
+    let passed = live
+        .and_then(|t| t.last_stop)
+        .and_then(|l| s.hints.times.get(&l))
+        .is_some_and(|l| l.departure >= at);
+    let delay = live.map_or(0, |t| t.delay);
+    let status = match live {
+        None => DepartureStatus::Scheduled,
+        Some(_) if passed => DepartureStatus::Departed,
+        Some(_) if delay > 0 => DepartureStatus::Delayed,
+        Some(_) if delay < 0 => DepartureStatus::Early,
+        Some(_) => DepartureStatus::OnTime,
+    };
+    Some(Departure {
+        stop: *stop,
+        trip: s.id.clone(),
+        route: s.hints.route,
+        name: route.map(|r| r.name.clone()).unwrap_or_default(),
+        code: route.map(|r| r.code.clone()).unwrap_or_default(),
+        color: route.map(|r| r.color.clone()).unwrap_or_default(),
+        headsign: trip.map(|t| t.headsign.clone()).unwrap_or_default(),
+        direction: s.hints.direction.clone(),
+        scheduled,
+        expected: scheduled + TimeDelta::minutes(delay as i64),
+        status,
+    })
+}
+
+#[cfg(feature = "db")]
+mod db {
+    use futures::TryStreamExt;
+    use mongodb::{bson::{doc, Document}, Database};
+    use serde::Deserialize;
+
+    use crate::{BrussType, Route, Schedule, StopKey, Trip, Type};
+
+    use super::{departure, Departure, DepartureQuery};
+
+    /// A schedule with its route and trip, as output by the pipeline.
+    #[derive(Deserialize)]
+    struct Row {
+        schedule: Schedule,
+        route: Option<Route>,
+        trip: Option<Trip>,
+    }
+
+    impl DepartureQuery {
+        /// Aggregation pipeline on the schedules collection giving the next departures from
+        /// `stop`.
+        ///
+        /// Schedules are first filtered on the indexable `hints.type`, `hints.times.<stop>` and
+        /// `arrival` fields, then on the time they leave the stop, which is computed from the
+        /// `[seconds, nanoseconds]` offset stored in `hints.times`. Trips ending at the stop are
+        /// the ones leaving it at their arrival time.
+        pub fn pipeline(&self, stop: &StopKey) -> mongodb::bson::ser::Result<Vec<Document>> {
+            let times = format!("hints.times.{}", stop.id);
+            let after = mongodb::bson::DateTime::from_chrono(self.after);
+            Ok(vec![
+                doc! { "$match": {
+                    "hints.type": mongodb::bson::to_bson(&stop.ty)?,
+                    &times: { "$exists": true },
+                    "arrival": { "$gte": after },
+                } },
+                doc! { "$set": { "at": { "$add": [
+                    "$departure",
+                    { "$multiply": [{ "$arrayElemAt": [format!("${}.departure", times), 0] }, 1000] },
+                ] } } },
+                // the arrival of a schedule is the time it leaves its last stop
+                doc! { "$match": { "at": { "$gte": after }, "$expr": { "$lt": ["$at", "$arrival"] } } },
+                doc! { "$sort": { "at": 1, "id": 1 } },
+                doc! { "$limit": self.limit as i64 },
+                doc! { "$project": { "_id": 0, "schedule": "$$ROOT" } },
+                doc! { "$lookup": { "from": Type::Route.collection(), "localField": "schedule.hints.route", "foreignField": "id", "as": "route" } },
+                doc! { "$lookup": { "from": Type::Trip.collection(), "localField": "schedule.id", "foreignField": "id", "as": "trip" } },
+                doc! { "$set": {
+                    "route": { "$arrayElemAt": ["$route", 0] },
+                    "trip": { "$arrayElemAt": ["$trip", 0] },
+                } },
+            ])
+        }
+
+        /// Runs the query on the database, with one aggregation per stop: each of them is limited
+        /// to `limit` departures before they are merged, sorted and limited again.
+        ///
+        /// `Trip::delay` isn't stored, so `live` looks up the live trip of a schedule, to take the
+        /// delay from. Without it, the stored trip is used, which is either on time, departed or
+        /// without realtime data.
+        pub async fn fetch<'a>(&self, db: &Database, live: impl Fn(&str) -> Option<&'a Trip>) -> mongodb::error::Result<Vec<Departure>> {
+            let mut o = Vec::new();
+            for stop in &self.stops {
+                let pipeline = self.pipeline(stop)?;
+                let mut cursor = Schedule::get_coll(db).aggregate(pipeline, None).await?;
+                while let Some(d) = cursor.try_next().await? {
+                    let row: Row = mongodb::bson::from_document(d)?;
+                    let trip = live(&row.schedule.id).or(row.trip.as_ref());
+                    o.extend(departure(stop, &row.schedule, row.route.as_ref(), trip));
+                }
+            }
+            o.retain(|d| d.scheduled >= self.after);
+            self.finish(&mut o);
+            Ok(o)
+        }
+    }
+}
+
+#[test]
+fn departure_test_run() {
+    use std::collections::HashMap;
+    use chrono::TimeZone;
+    use tt::AreaType;
+    use crate::{test_util, Path, RoutingType};
+
+    let path = Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus);
+    let trip = |id: &str, delay, last_stop: u16| Trip {
+        delay,
+        last_stop: (last_stop != 0).then_some(last_stop),
+        headsign: "Centro".to_string(),
+        last_event: (last_stop != 0).then(Utc::now),
+        ..test_util::trip(id, 5, &path)
+    };
+    let trips = HashMap::from([
+        ("a".to_string(), trip("a", 0, 0)),
+        ("b".to_string(), trip("b", 3, 1)),
+        ("c".to_string(), trip("c", 0, 2)),
+    ]);
+    let at = |h, m| Utc.with_ymd_and_hms(2024, 5, 1, h, m, 0).unwrap();
+    let schedules = [("a", at(8, 0)), ("b", at(8, 20)), ("c", at(8, 10)), ("a", at(7, 0))]
+        .map(|(id, departure)| Schedule::from_trip(&trips[id], departure).unwrap());
+    let route = Route::new(5, 3, 1, AreaType::U, "ff0000".to_string(), "5".to_string(), "5".to_string());
+
+    let run = |q: DepartureQuery| q.run(&schedules, |id| (id == 5).then_some(&route), |id| trips.get(id));
+    let d = run(DepartureQuery::new(StopKey::new(AreaType::U, 2), at(8, 0)));
+    assert_eq!(d.iter().map(|d| d.trip.as_str()).collect::<Vec<_>>(), vec!["a", "c", "b"]);
+    assert_eq!(d[0].status, DepartureStatus::Scheduled);
+    assert_eq!(d[0].color, "ff0000");
+    assert_eq!(d[1].status, DepartureStatus::Departed);
+    assert_eq!((d[2].status, d[2].expected), (DepartureStatus::Delayed, at(8, 28)));
+
+    // trips end at stop 3
+    assert!(run(DepartureQuery::new(StopKey::new(AreaType::U, 3), at(8, 0))).is_empty());
+    assert_eq!(run(DepartureQuery::new(StopKey::new(AreaType::U, 1), at(8, 0)).limit(1)).len(), 1);
+}
+
+#[cfg(feature = "db")]
+#[test]
+fn departure_test_pipeline() {
+    use chrono::TimeZone;
+    use mongodb::bson::doc;
+    use tt::AreaType;
+
+    let at = Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap();
+    let pipeline = DepartureQuery::new(StopKey::new(AreaType::U, 2), at).limit(3).pipeline(&StopKey::new(AreaType::U, 2)).unwrap();
+    assert_eq!(pipeline.len(), 9);
+    assert_eq!(pipeline[0].get_document("$match").unwrap().get_document("hints.times.2").unwrap(), &doc! { "$exists": true });
+    assert_eq!(pipeline[4], doc! { "$limit": 3_i64 });
+}
@@ -0,0 +1,149 @@
+use std::collections::BTreeSet;
+
+use serde::Serialize;
+
+use crate::{map::align, AreaHelper, Dataset, Direction, RouteVariants, StopKey, StopRoutes, VariantKind};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiagramStop {
+    pub stop: StopKey,
+    /// Empty if the stop is unknown.
+    pub name: String,
+    /// Column the stop is drawn in: 0 for the stops of the main variant, the track of the first
+    /// branch serving it otherwise.
+    pub track: usize,
+    /// First or last stop of at least one branch.
+    pub terminus: bool,
+    /// Other routes stopping here, sorted.
+    pub interchanges: Vec<u16>,
+}
+
+/// A variant of the route, drawn as a line through its stops.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct DiagramBranch {
+    pub path: String,
+    pub kind: VariantKind,
+    /// Column of the stops this branch adds to the diagram; 0 if all its stops are served by the
+    /// branches before it.
+    pub track: usize,
+    /// Indexes in `LineDiagram::stops` of the stops of the branch, in order.
+    pub stops: Vec<usize>,
+    /// Ids of the trips following the branch, sorted.
+    pub trips: Vec<String>,
+}
+
+/// # LineDiagram
+/// Data for a schematic, vertical strip diagram of a route in one direction.
+///
+/// `stops` are the rows of the diagram, in the canonical order of `RouteVariants`. The main
+/// variant is the first branch and runs along track 0; every other branch adding stops of its own
+/// gets a new track for them, and joins track 0 where it shares stops with the main variant.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct LineDiagram {
+    pub route: u16,
+    /// Code, name and color of the route, empty if the route is unknown.
+    pub code: String,
+    pub name: String,
+    pub color: String,
+    pub direction: Direction,
+    pub stops: Vec<DiagramStop>,
+    pub branches: Vec<DiagramBranch>,
+    /// Number of tracks, the main one included.
+    pub tracks: usize,
+}
+
+impl LineDiagram {
+    /// Builds the diagram of the route analyzed in `variants`, taking stops, routes and trips from
+    /// `dataset`, and the routes of each stop from `index` (see `StopRoutes::from_dataset`).
+    pub fn new(variants: &RouteVariants, dataset: &Dataset, index: &AreaHelper<StopRoutes>) -> Self {
+        let sequence = &variants.sequence;
+        let mut tracks = vec![None; sequence.len()];
+        let mut terminus = vec![false; sequence.len()];
+        let mut count = 1;
+        let branches = variants.variants.iter()
+            .map(|v| {
+                let rows = align(sequence, &v.sequence);
+                let new = rows.iter().filter(|r| tracks[**r].is_none()).copied().collect::<Vec<_>>();
+                let track = if v.kind == VariantKind::Main || new.is_empty() {
+                    0
+                } else {
+                    count += 1;
+                    count - 1
+                };
+                for r in new {
+                    tracks[r] = Some(track);
+                }
+                for r in [rows.first(), rows.last()].into_iter().flatten() {
+                    terminus[*r] = true;
+                }
+                let mut trips = dataset.trips_of_path(&v.path)
+                    .filter(|t| t.route == variants.route && t.direction == variants.direction)
+                    .map(|t| t.id.clone())
+                    .collect::<Vec<_>>();
+                trips.sort();
+                DiagramBranch { path: v.path.clone(), kind: v.kind, track, stops: rows, trips }
+            })
+            .collect();
+
+        let stops = sequence.iter().enumerate()
+            .map(|(r, id)| {
+                let stop = StopKey::new(variants.ty, *id);
+                let interchanges = index.get_key(&stop).into_iter()
+                    .flat_map(|s| s.routes.iter().map(|served| served.route))
+                    .filter(|route| *route != variants.route)
+                    .collect::<BTreeSet<_>>();
+                DiagramStop {
+                    stop,
+                    name: dataset.stop(&stop).map(|s| s.name.clone()).unwrap_or_default(),
+                    track: tracks[r].unwrap_or(0),
+                    terminus: terminus[r],
+                    interchanges: interchanges.into_iter().collect(),
+                }
+            })
+            .collect();
+
+        let route = dataset.route(variants.route);
+        Self {
+            route: variants.route,
+            code: route.map(|r| r.code.clone()).unwrap_or_default(),
+            name: route.map(|r| r.name.clone()).unwrap_or_default(),
+            color: route.map(|r| r.color.clone()).unwrap_or_default(),
+            direction: variants.direction.clone(),
+            stops,
+            branches,
+            tracks: count,
+        }
+    }
+
+    /// The diagram of `route` in `direction`, with the interchanges found in the trips of
+    /// `dataset`. Returns `None` if the route has no trips in that direction.
+    pub fn from_dataset(dataset: &Dataset, route: u16, direction: Direction) -> Option<Self> {
+        let variants = RouteVariants::from_dataset(dataset, route, direction)?;
+        Some(Self::new(&variants, dataset, &StopRoutes::from_dataset(dataset)))
+    }
+}
+
+#[test]
+fn diagram_test_new() {
+    use tt::AreaType;
+    use crate::{test_util::trip, Path, RoutingType};
+
+    let main = Path::new(vec![1, 2, 3, 4], AreaType::U, RoutingType::Bus);
+    let branch = Path::new(vec![1, 2, 5], AreaType::U, RoutingType::Bus);
+    let short = Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus);
+    let other = Path::new(vec![6, 2, 7], AreaType::U, RoutingType::Bus);
+
+    let mut d = Dataset::new();
+    d.add_trips([trip("a", 5, &main), trip("b", 5, &main), trip("c", 5, &branch), trip("d", 5, &short), trip("e", 8, &other)]);
+    d.add_paths([main, branch, short, other]);
+
+    let diagram = LineDiagram::from_dataset(&d, 5, Direction::Forward).unwrap();
+    assert_eq!(diagram.stops.iter().map(|s| (s.stop.id, s.track)).collect::<Vec<_>>(), vec![(1, 0), (2, 0), (3, 0), (4, 0), (5, 1)]);
+    assert_eq!(diagram.tracks, 2);
+    assert_eq!(diagram.branches[0].trips, vec!["a", "b"]);
+    let branch = diagram.branches.iter().find(|b| b.kind == VariantKind::Branch).unwrap();
+    assert_eq!((branch.track, branch.stops.clone()), (1, vec![0, 1, 4]));
+    assert!(diagram.branches.iter().any(|b| b.kind == VariantKind::Prefix && b.track == 0));
+    assert_eq!(diagram.stops[1].interchanges, vec![8]);
+    assert!(diagram.stops[2].terminus && !diagram.stops[1].terminus);
+}
@@ -0,0 +1,306 @@
+use std::{collections::BTreeSet, fmt::Display};
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::{Coords, Dataset, Route, StopKey, Trip};
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StopMove {
+    pub stop: StopKey,
+    pub from: Coords,
+    pub to: Coords,
+    /// Meters.
+    pub distance: f64,
+}
+
+/// Old and new value of a changed field.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct FieldChange<T> {
+    pub old: T,
+    pub new: T,
+}
+
+impl<T: PartialEq + Clone> FieldChange<T> {
+    fn of(old: &T, new: &T) -> Option<Self> {
+        (old != new).then(|| Self { old: old.clone(), new: new.clone() })
+    }
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RouteChange {
+    pub route: u16,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub name: Option<FieldChange<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub code: Option<FieldChange<String>>,
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub color: Option<FieldChange<String>>,
+}
+
+/// Difference of the times of a stop: positive if the trip gets there later than before.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct StopShift {
+    pub stop: u16,
+    pub arrival: TimeDelta,
+    pub departure: TimeDelta,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TripChange {
+    pub trip: String,
+    /// Set if the trip follows another path.
+    #[serde(skip_serializing_if = "Option::is_none")]
+    pub path: Option<FieldChange<String>>,
+    /// Stops served by both versions whose times changed.
+    pub shifts: Vec<StopShift>,
+}
+
+/// Changes to the departures (`Schedule`s) of a trip.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct ScheduleChange {
+    pub trip: String,
+    pub added: Vec<DateTime<Utc>>,
+    pub removed: Vec<DateTime<Utc>>,
+    /// Departures replaced by another one: each removed departure of the trip is paired with the
+    /// closest added one at most [`DatasetDiff::MAX_RESCHEDULE`] minutes away, closest pairs
+    /// first. Only the unpaired ones are left in `added` or `removed`.
+    pub moved: Vec<FieldChange<DateTime<Utc>>>,
+}
+
+/// # DatasetDiff
+/// What changed between two imports of the timetable, to review a new import before it replaces
+/// the old one.
+///
+/// It serializes to a machine-readable report, while `Display` gives a short human-readable
+/// summary. All the lists are sorted by id.
+#[derive(Serialize, Debug, Clone, Default)]
+pub struct DatasetDiff {
+    pub stops_added: Vec<StopKey>,
+    pub stops_removed: Vec<StopKey>,
+    pub stops_moved: Vec<StopMove>,
+    pub routes_added: Vec<u16>,
+    pub routes_removed: Vec<u16>,
+    pub routes_changed: Vec<RouteChange>,
+    pub paths_added: Vec<String>,
+    pub paths_removed: Vec<String>,
+    pub trips_added: Vec<String>,
+    pub trips_removed: Vec<String>,
+    pub trips_changed: Vec<TripChange>,
+    pub schedules_changed: Vec<ScheduleChange>,
+}
+
+impl DatasetDiff {
+    /// Stops moved by less than this (in meters) are considered in the same place.
+    pub const MIN_MOVE: f64 = 1.;
+    /// Departures further apart than this (in minutes) are reported as removed and added, not moved.
+    pub const MAX_RESCHEDULE: i64 = 30;
+
+    pub fn between(old: &Dataset, new: &Dataset) -> Self {
+        let mut o = Self::default();
+
+        let (old_stops, new_stops) = (keys(old.stops().values().map(|s| s.key())), keys(new.stops().values().map(|s| s.key())));
+        o.stops_added = new_stops.difference(&old_stops).copied().collect();
+        o.stops_removed = old_stops.difference(&new_stops).copied().collect();
+        o.stops_moved = old_stops.intersection(&new_stops)
+            .filter_map(|k| {
+                let (a, b) = (&old.stop(k)?.position, &new.stop(k)?.position);
+                let distance = a.haversine(b);
+                (distance >= Self::MIN_MOVE).then(|| StopMove { stop: *k, from: a.clone(), to: b.clone(), distance })
+            })
+            .collect();
+
+        let (old_routes, new_routes) = (keys(old.routes().map(|r| r.id)), keys(new.routes().map(|r| r.id)));
+        o.routes_added = new_routes.difference(&old_routes).copied().collect();
+        o.routes_removed = old_routes.difference(&new_routes).copied().collect();
+        o.routes_changed = old_routes.intersection(&new_routes)
+            .filter_map(|id| route_change(old.route(*id)?, new.route(*id)?))
+            .collect();
+
+        let (old_paths, new_paths) = (keys(old.paths().map(|p| p.id.clone())), keys(new.paths().map(|p| p.id.clone())));
+        o.paths_added = new_paths.difference(&old_paths).cloned().collect();
+        o.paths_removed = old_paths.difference(&new_paths).cloned().collect();
+
+        let (old_trips, new_trips) = (keys(old.trips().map(|t| t.id.clone())), keys(new.trips().map(|t| t.id.clone())));
+        o.trips_added = new_trips.difference(&old_trips).cloned().collect();
+        o.trips_removed = old_trips.difference(&new_trips).cloned().collect();
+        o.trips_changed = old_trips.intersection(&new_trips)
+            .filter_map(|id| trip_change(old.trip(id)?, new.trip(id)?))
+            .collect();
+
+        let scheduled = keys(old.schedules().iter().chain(new.schedules()).map(|s| s.id.clone()));
+        o.schedules_changed = scheduled.into_iter()
+            .filter_map(|id| schedule_change(old, new, id))
+            .collect();
+        o
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.stops_added.is_empty() && self.stops_removed.is_empty() && self.stops_moved.is_empty()
+            && self.routes_added.is_empty() && self.routes_removed.is_empty() && self.routes_changed.is_empty()
+            && self.paths_added.is_empty() && self.paths_removed.is_empty()
+            && self.trips_added.is_empty() && self.trips_removed.is_empty() && self.trips_changed.is_empty()
+            && self.schedules_changed.is_empty()
+    }
+}
+
+fn keys<K: Ord>(iter: impl Iterator<Item = K>) -> BTreeSet<K> {
+    iter.collect()
+}
+
+fn route_change(old: &Route, new: &Route) -> Option<RouteChange> {
+    let o = RouteChange {
+        route: old.id,
+        name: FieldChange::of(&old.name, &new.name),
+        code: FieldChange::of(&old.code, &new.code),
+        color: FieldChange::of(&old.color, &new.color),
+    };
+    (o.name.is_some() || o.code.is_some() || o.color.is_some()).then_some(o)
+}
+
+fn trip_change(old: &Trip, new: &Trip) -> Option<TripChange> {
+    let mut shifts = old.times.iter()
+        .filter_map(|(stop, a)| {
+            let b = new.times.get(stop)?;
+            let (arrival, departure) = (b.arrival - a.arrival, b.departure - a.departure);
+            (!arrival.is_zero() || !departure.is_zero()).then_some(StopShift { stop: *stop, arrival, departure })
+        })
+        .collect::<Vec<_>>();
+    shifts.sort_by_key(|s| s.stop);
+    let path = FieldChange::of(&old.path, &new.path);
+    (path.is_some() || !shifts.is_empty()).then(|| TripChange { trip: old.id.clone(), path, shifts })
+}
+
+fn schedule_change(old: &Dataset, new: &Dataset, trip: String) -> Option<ScheduleChange> {
+    let (a, b) = (keys(old.schedules_of_trip(&trip).map(|s| s.departure)), keys(new.schedules_of_trip(&trip).map(|s| s.departure)));
+    let mut removed = a.difference(&b).copied().collect::<BTreeSet<_>>();
+    let mut added = b.difference(&a).copied().collect::<BTreeSet<_>>();
+    let max = TimeDelta::minutes(DatasetDiff::MAX_RESCHEDULE);
+    let mut pairs = removed.iter()
+        .flat_map(|old| added.iter().map(move |new| ((*new - *old).abs(), *old, *new)))
+        .filter(|(d, _, _)| *d <= max)
+        .collect::<Vec<_>>();
+    pairs.sort();
+    let mut moved = Vec::new();
+    for (_, old, new) in pairs {
+        if removed.contains(&old) && added.contains(&new) {
+            removed.remove(&old);
+            added.remove(&new);
+            moved.push(FieldChange { old, new });
+        }
+    }
+    moved.sort_by_key(|m| m.old);
+    let (added, removed) = (added.into_iter().collect::<Vec<_>>(), removed.into_iter().collect::<Vec<_>>());
+    (!added.is_empty() || !removed.is_empty() || !moved.is_empty()).then_some(ScheduleChange { trip, added, removed, moved })
+}
+
+impl Display for DatasetDiff {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if self.is_empty() {
+            return writeln!(f, "no changes");
+        }
+        writeln!(f, "stops: {} added, {} removed, {} moved", self.stops_added.len(), self.stops_removed.len(), self.stops_moved.len())?;
+        for m in &self.stops_moved {
+            writeln!(f, "  stop {} moved by {:.0}m", m.stop, m.distance)?;
+        }
+        writeln!(f, "routes: {} added, {} removed, {} changed", self.routes_added.len(), self.routes_removed.len(), self.routes_changed.len())?;
+        for r in &self.routes_changed {
+            write!(f, "  route {}:", r.route)?;
+            for (field, c) in [("name", &r.name), ("code", &r.code), ("color", &r.color)] {
+                if let Some(c) = c {
+                    write!(f, " {} {:?} -> {:?}", field, c.old, c.new)?;
+                }
+            }
+            writeln!(f)?;
+        }
+        writeln!(f, "paths: {} added, {} removed", self.paths_added.len(), self.paths_removed.len())?;
+        let rerouted = self.trips_changed.iter().filter(|t| t.path.is_some()).count();
+        let shifted = self.trips_changed.iter().filter(|t| !t.shifts.is_empty()).count();
+        writeln!(f, "trips: {} added, {} removed, {} rerouted, {} with shifted times", self.trips_added.len(), self.trips_removed.len(), rerouted, shifted)?;
+        let max = self.trips_changed.iter()
+            .flat_map(|t| t.shifts.iter().map(move |s| (s.departure.abs(), t, s)))
+            .max_by_key(|(d, _, _)| *d);
+        if let Some((d, t, s)) = max {
+            writeln!(f, "  largest shift: {}s at stop {} of trip {}", d.num_seconds(), s.stop, t.trip)?;
+        }
+        let (added, removed, moved) = self.schedules_changed.iter()
+            .fold((0, 0, 0), |(a, r, m), c| (a + c.added.len(), r + c.removed.len(), m + c.moved.len()));
+        writeln!(f, "departures: {} added, {} removed, {} moved, in {} trips", added, removed, moved, self.schedules_changed.len())?;
+        Ok(())
+    }
+}
+
+#[test]
+fn diff_test_between() {
+    use chrono::TimeZone;
+    use tt::AreaType;
+    use crate::{stop_time::StopTime, test_util, Path, RoutingType, Schedule};
+
+    let stop = |id, lat| test_util::stop(AreaType::U, id, "", Coords::new(lat, 11.12));
+    let route = |color: &str| Route::new(5, 3, 1, AreaType::U, color.to_string(), "5".to_string(), "5".to_string());
+    let path = Path::new(vec![1, 2], AreaType::U, RoutingType::Bus);
+    let trip = |departure| {
+        let mut t = test_util::trip("a", 5, &path);
+        t.times.0.insert(2, StopTime { arrival: TimeDelta::minutes(departure), departure: TimeDelta::minutes(departure) });
+        t
+    };
+
+    let mut old = Dataset::new();
+    old.add_stops([stop(1, 46.07), stop(2, 46.08)]);
+    old.add_routes([route("ff0000")]);
+    let at = |h| Utc.with_ymd_and_hms(2024, 5, 1, h, 0, 0).unwrap();
+    old.add_schedules([Schedule::from_trip(&trip(5), at(8)).unwrap(), Schedule::from_trip(&trip(5), at(9)).unwrap()]);
+    old.add_trips([trip(5)]);
+    let mut new = Dataset::new();
+    new.add_stops([stop(1, 46.0701), stop(3, 46.09)]);
+    new.add_routes([route("00ff00")]);
+    new.add_schedules([Schedule::from_trip(&trip(7), at(9)).unwrap(), Schedule::from_trip(&trip(7), at(10)).unwrap(), Schedule::from_trip(&trip(7), at(11)).unwrap()]);
+    new.add_trips([trip(7)]);
+
+    let d = DatasetDiff::between(&old, &new);
+    assert_eq!(d.stops_added, vec![StopKey::new(AreaType::U, 3)]);
+    assert_eq!(d.stops_removed, vec![StopKey::new(AreaType::U, 2)]);
+    assert_eq!(d.stops_moved.len(), 1);
+    assert!((d.stops_moved[0].distance - 11.1).abs() < 0.1);
+    assert_eq!(d.routes_changed[0].color, Some(FieldChange { old: "ff0000".to_string(), new: "00ff00".to_string() }));
+    assert_eq!(d.routes_changed[0].name, None);
+    assert_eq!(d.trips_changed[0].shifts, vec![StopShift { stop: 2, arrival: TimeDelta::minutes(2), departure: TimeDelta::minutes(2) }]);
+    assert_eq!(d.schedules_changed, vec![ScheduleChange {
+        trip: "a".to_string(),
+        added: vec![at(10), at(11)],
+        removed: vec![at(8)],
+        moved: vec![],
+    }]);
+    assert!(d.to_string().contains("route 5: color \"ff0000\" -> \"00ff00\""));
+    assert!(d.to_string().contains("departures: 2 added, 1 removed, 0 moved, in 1 trips"));
+    assert!(DatasetDiff::between(&new, &new).is_empty());
+}
+
+#[test]
+fn diff_test_rescheduled() {
+    use chrono::TimeZone;
+    use tt::AreaType;
+    use crate::{test_util::trip, Path, RoutingType, Schedule};
+
+    let path = Path::new(vec![1, 2], AreaType::U, RoutingType::Bus);
+    let trip = trip("a", 5, &path);
+    let at = |h, m| Utc.with_ymd_and_hms(2024, 5, 1, h, m, 0).unwrap();
+    let dataset = |departures: &[DateTime<Utc>]| {
+        let mut d = Dataset::new();
+        d.add_schedules(departures.iter().map(|t| Schedule::from_trip(&trip, *t).unwrap()));
+        d
+    };
+
+    // the 12:00 departure moved, the 08:00 one is gone
+    let d = DatasetDiff::between(&dataset(&[at(8, 0), at(12, 0)]), &dataset(&[at(12, 30)]));
+    assert_eq!(d.schedules_changed, vec![ScheduleChange {
+        trip: "a".to_string(),
+        added: vec![],
+        removed: vec![at(8, 0)],
+        moved: vec![FieldChange { old: at(12, 0), new: at(12, 30) }],
+    }]);
+
+    let d = DatasetDiff::between(&dataset(&[at(8, 0), at(9, 0)]), &dataset(&[at(8, 10), at(9, 5)]));
+    assert_eq!(d.schedules_changed[0].moved, vec![FieldChange { old: at(8, 0), new: at(8, 10) }, FieldChange { old: at(9, 0), new: at(9, 5) }]);
+    assert!(d.schedules_changed[0].added.is_empty() && d.schedules_changed[0].removed.is_empty());
+}
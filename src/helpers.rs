@@ -1,8 +1,8 @@
 use tt::AreaType;
 use AreaType::{U, E};
 
-use crate::InArea;
-use std::collections::HashMap;
+use crate::{InArea, StopKey};
+use std::{cmp::Ordering, collections::HashMap};
 
 
 #[derive(Default)]
@@ -54,6 +54,15 @@ impl<T: InArea> AreaHelper<T> {
     pub fn len(&self) -> usize {
         self.urban.len() + self.extra.len()
     }
+
+    pub fn get_key(&self, key: &StopKey) -> Option<&T> {
+        self.get(key.ty).get(&key.id)
+    }
+
+    /// Iterates over the items of both areas.
+    pub fn values(&self) -> impl Iterator<Item = &T> {
+        self.urban.values().chain(self.extra.values())
+    }
 }
 
 impl<T: InArea> FromIterator<T> for AreaHelper<T> {
@@ -66,3 +75,30 @@ impl<T: InArea> FromIterator<T> for AreaHelper<T> {
     }
 }
 
+
+/// Entry of a `BinaryHeap` used as a min-heap by cost, for Dijkstra-like searches.
+pub(crate) struct MinCost<N> {
+    pub cost: f64,
+    pub node: N,
+}
+
+impl<N: Ord> PartialEq for MinCost<N> {
+    fn eq(&self, other: &Self) -> bool {
+        self.cmp(other) == Ordering::Equal
+    }
+}
+
+impl<N: Ord> Eq for MinCost<N> {}
+
+impl<N: Ord> PartialOrd for MinCost<N> {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl<N: Ord> Ord for MinCost<N> {
+    // reversed, to make `BinaryHeap` a min-heap
+    fn cmp(&self, other: &Self) -> Ordering {
+        other.cost.total_cmp(&self.cost).then_with(|| other.node.cmp(&self.node))
+    }
+}
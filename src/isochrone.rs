@@ -0,0 +1,196 @@
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::{AreaHelper, Coords, Planner, Stop, StopKey, TransferGenerator};
+
+/// Where an isochrone starts from.
+#[derive(Debug, Clone)]
+pub enum Origin {
+    Stop(StopKey),
+    /// Any position: the stops within walking distance are reached on foot.
+    Coords(Coords),
+}
+
+/// A stop reachable from the origin of an `Isochrone`.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Reached {
+    pub stop: StopKey,
+    pub position: Coords,
+    pub arrival: DateTime<Utc>,
+}
+
+/// # IsochroneSearch
+/// Finds what can be reached from an origin within a time budget, riding the trips known to a
+/// `Planner` and walking with the model of a `TransferGenerator`.
+pub struct IsochroneSearch<'a> {
+    planner: &'a Planner,
+    stops: &'a AreaHelper<Stop>,
+    walking: TransferGenerator,
+    wheelchair: bool,
+    max_transfers: usize,
+}
+
+impl<'a> IsochroneSearch<'a> {
+    pub fn new(planner: &'a Planner, stops: &'a AreaHelper<Stop>) -> Self {
+        Self { planner, stops, walking: TransferGenerator::default(), wheelchair: false, max_transfers: crate::Query::DEFAULT_MAX_TRANSFERS }
+    }
+
+    pub fn walking(mut self, walking: TransferGenerator) -> Self {
+        self.walking = walking;
+        self
+    }
+
+    /// Only board and alight at stops with `wheelchair_boarding`.
+    pub fn wheelchair(mut self, wheelchair: bool) -> Self {
+        self.wheelchair = wheelchair;
+        self
+    }
+
+    pub fn max_transfers(mut self, max_transfers: usize) -> Self {
+        self.max_transfers = max_transfers;
+        self
+    }
+
+    /// Stops reachable from `origin` leaving at `departure`, arriving within `duration`.
+    /// Returns `None` if the origin is an unknown stop.
+    pub fn run(&self, origin: &Origin, departure: DateTime<Utc>, duration: TimeDelta) -> Option<Isochrone> {
+        let deadline = departure + duration;
+        let (position, sources) = match origin {
+            Origin::Stop(key) => (self.stops.get_key(key)?.position.clone(), vec![(*key, departure)]),
+            Origin::Coords(c) => {
+                let sources = self.stops.values()
+                    .map(|s| (s.key(), departure + self.walking.walk_time(c, &s.position)))
+                    .filter(|(_, t)| *t <= deadline)
+                    .collect();
+                (c.clone(), sources)
+            }
+        };
+        let stops = self.planner.earliest_arrivals(&sources, deadline, self.max_transfers + 1, self.wheelchair)
+            .into_iter()
+            .filter_map(|(stop, arrival)| Some(Reached { stop, position: self.stops.get_key(&stop)?.position.clone(), arrival }))
+            .collect();
+        Some(Isochrone { origin: position, departure, deadline, stops, walking: self.walking })
+    }
+}
+
+/// # Isochrone
+/// The stops reachable from an origin before a deadline.
+///
+/// With the `isochrone` feature, it can be turned into the area reachable by walking from the
+/// origin and from those stops with the remaining time.
+#[derive(Debug, Clone)]
+pub struct Isochrone {
+    pub origin: Coords,
+    pub departure: DateTime<Utc>,
+    pub deadline: DateTime<Utc>,
+    /// Reached stops, sorted by key.
+    pub stops: Vec<Reached>,
+    walking: TransferGenerator,
+}
+
+#[cfg(feature = "isochrone")]
+impl Isochrone {
+    /// Number of vertices of the polygon approximating each walking circle.
+    const CIRCLE_VERTICES: usize = 32;
+
+    /// Union of the circles that can be walked from the origin and from every reached stop.
+    pub fn polygon(&self) -> geo::MultiPolygon<f64> {
+        use geo::BooleanOps;
+
+        std::iter::once((&self.origin, self.departure))
+            .chain(self.stops.iter().map(|r| (&r.position, r.arrival)))
+            .map(|(c, t)| (c, self.walking.reach(self.deadline - t)))
+            .filter(|(_, r)| *r > 0.)
+            .fold(geo::MultiPolygon::new(Vec::new()), |acc, (c, r)| acc.union(&geo::MultiPolygon::new(vec![circle(c, r)])))
+    }
+
+    /// GeoJSON `FeatureCollection` with the isochrone polygon and a point for every reached stop.
+    pub fn to_geojson(&self) -> serde_json::Value {
+        use serde_json::json;
+
+        let ring = |l: &geo::LineString<f64>| l.coords().map(|c| [c.x, c.y]).collect::<Vec<_>>();
+        let polygons = self.polygon().0.iter()
+            .map(|p| std::iter::once(p.exterior()).chain(p.interiors()).map(ring).collect::<Vec<_>>())
+            .collect::<Vec<_>>();
+        let mut features = vec![json!({
+            "type": "Feature",
+            "geometry": { "type": "MultiPolygon", "coordinates": polygons },
+            "properties": {
+                "departure": self.departure.to_rfc3339(),
+                "deadline": self.deadline.to_rfc3339(),
+            },
+        })];
+        features.extend(self.stops.iter().map(|r| json!({
+            "type": "Feature",
+            "geometry": { "type": "Point", "coordinates": [r.position.lng, r.position.lat] },
+            "properties": {
+                "type": r.stop.ty,
+                "id": r.stop.id,
+                "arrival": r.arrival.to_rfc3339(),
+            },
+        })));
+        json!({ "type": "FeatureCollection", "features": features })
+    }
+}
+
+/// Polygon approximating the circle of `radius` meters around `center`.
+#[cfg(feature = "isochrone")]
+fn circle(center: &Coords, radius: f64) -> geo::Polygon<f64> {
+    let dlat = (radius / Coords::EARTH_RADIUS).to_degrees();
+    let dlng = dlat / center.lat.to_radians().cos();
+    let mut points = (0..Isochrone::CIRCLE_VERTICES)
+        .map(|i| {
+            let a = std::f64::consts::TAU * i as f64 / Isochrone::CIRCLE_VERTICES as f64;
+            (center.lng + dlng * a.sin(), center.lat + dlat * a.cos())
+        })
+        .collect::<Vec<_>>();
+    points.push(points[0]);
+    geo::Polygon::new(points.into(), Vec::new())
+}
+
+#[test]
+fn isochrone_test_run() {
+    use tt::AreaType;
+    use crate::{stop_time::{StopTime, StopTimes}, test_util::stop, Direction, Path, RoutingType, Schedule, ScheduleHints};
+
+    let k = |id| StopKey::new(AreaType::U, id);
+    // stops are about 1.1km apart, too far to walk between them in 15 minutes
+    let stops = AreaHelper::from_iter((1..=4).map(|id| stop(AreaType::U, id, "", Coords::new(46.07 + id as f64 * 0.01, 11.12))));
+    let day = DateTime::parse_from_rfc3339("2024-05-06T00:00:00Z").unwrap().with_timezone(&Utc);
+    let hints = |times: &[(u16, i64)]| ScheduleHints {
+        route: 1,
+        ty: AreaType::U,
+        direction: Direction::Forward,
+        times: StopTimes(times.iter().map(|(s, t)| (*s, StopTime { arrival: TimeDelta::minutes(*t), departure: TimeDelta::minutes(*t) })).collect()),
+    };
+    let departure = day + TimeDelta::hours(8);
+    let schedule = Schedule { id: "a".to_string(), departure, arrival: departure + TimeDelta::minutes(20), hints: hints(&[(1, 0), (2, 5), (3, 20)]) };
+
+    let mut planner = Planner::new(&stops);
+    assert!(planner.add_schedule(&schedule, &Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus)));
+    planner.add_footpath(k(2), k(4), TimeDelta::minutes(3));
+
+    let search = IsochroneSearch::new(&planner, &stops);
+    let iso = search.run(&Origin::Stop(k(1)), departure, TimeDelta::minutes(15)).unwrap();
+    let reached = iso.stops.iter().map(|r| (r.stop, r.arrival)).collect::<Vec<_>>();
+    assert_eq!(reached, vec![
+        (k(1), departure),
+        (k(2), departure + TimeDelta::minutes(5)),
+        (k(4), departure + TimeDelta::minutes(8)),
+    ]);
+
+    // walking 500m south of stop 1 takes about 8 minutes: the bus is already gone
+    let iso = search.run(&Origin::Coords(Coords::new(46.0755, 11.12)), departure, TimeDelta::minutes(15)).unwrap();
+    assert_eq!(iso.stops.len(), 1);
+
+    #[cfg(feature = "isochrone")]
+    {
+        use geo::Contains;
+
+        let polygon = iso.polygon();
+        assert!(polygon.contains(&geo::Point::new(11.12, 46.0755)));
+        assert!(polygon.contains(&geo::Point::new(11.12, 46.08)));
+        assert!(!polygon.contains(&geo::Point::new(11.12, 46.09)));
+        assert_eq!(iso.to_geojson()["features"].as_array().unwrap().len(), 2);
+    }
+}
@@ -6,25 +6,61 @@ mod map;
 mod trip;
 mod helpers;
 mod stop_time;
+mod batch;
+mod network;
+mod planner;
+mod transfer;
+mod isochrone;
+mod dataset;
+mod diff;
+mod search;
+mod station;
+mod stop_routes;
+mod departure;
+mod timetable;
+mod variant;
+mod diagram;
 // mod log;
 mod ty;
+#[cfg(test)]
+mod test_util;
 
-#[cfg(feature = "db")]
 mod schedule;
-#[cfg(feature = "db")]
 pub use schedule::{Schedule, ScheduleHints};
+#[cfg(feature = "db")]
+pub mod watch;
+#[cfg(feature = "schema")]
+pub mod schema;
 
 pub use area::Area;
 pub use ty::Type;
 pub use route::Route;
-pub use stop::{Stop,StopPair};
+pub use stop::{Stop,StopPair,StopKey};
 pub use coords::Coords;
-pub use map::{Segment,Path,RoutingType,sequence_hash};
+pub use map::{Segment,SegmentSource,Fallback,Interpolation,BBox,SegmentValidator,SegmentReport,Thresholds,Issue,Path,RoutingType,PathIdMigration,PathRegistry,PathCollision,StalePath,IngestError,HashVersion,sequence_hash,sequence_uuid};
 #[cfg(feature = "polyline")]
 pub use map::polyline::PolySegment;
-pub use trip::{Trip,Direction};
+#[cfg(feature = "osrm")]
+pub use map::osrm;
+#[cfg(feature = "railway")]
+pub use map::railway;
+pub use trip::{Trip,Direction,TripError};
 pub use stop_time::{StopTime,StopTimes};
 pub use helpers::AreaHelper;
+pub use batch::{Batch,BatchOutput,RecordError};
+pub use network::{Network,Edge};
+pub use planner::{Planner,Query,Journey,Leg};
+pub use transfer::{Transfer,TransferGenerator};
+pub use isochrone::{Isochrone,IsochroneSearch,Origin,Reached};
+pub use dataset::{Dataset,IntegrityReport,Violation};
+pub use diff::{DatasetDiff,StopMove,RouteChange,TripChange,StopShift,ScheduleChange,FieldChange};
+pub use search::{StopIndex,StopQuery,Hit,Normalizer,fold};
+pub use station::{Station,StationClustering};
+pub use stop_routes::{StopRoutes,ServedRoute};
+pub use departure::{Departure,DepartureQuery,DepartureStatus};
+pub use timetable::{Timetable,TimetableQuery,TimetableStop,TimetableTrip,TimetableCell};
+pub use variant::{RouteVariants,Variant,VariantKind};
+pub use diagram::{LineDiagram,DiagramStop,DiagramBranch};
 
 use serde::{de::DeserializeOwned, Serialize};
 
@@ -0,0 +1,78 @@
+use crate::{Coords, Stop};
+use super::{Segment, SegmentSource};
+
+/// How points are placed between the two ends of a fallback segment.
+#[derive(Clone,Copy,Debug,Default,PartialEq,Eq)]
+pub enum Interpolation {
+    /// Straight line in the lat/lng plane, as it is drawn on a web mercator map.
+    Linear,
+    /// Great circle arc, the shortest path on the earth surface.
+    #[default]
+    GreatCircle,
+}
+
+/// # Fallback
+/// Generates segment geometries without a routing engine, for the stop pairs that can't be
+/// routed: cableways, or bus pairs the router failed on.
+///
+/// The geometry is a line between the two stops, densified so that consecutive points are at most
+/// `spacing` meters apart. Generated segments have `SegmentSource::Fallback`, so they can be found
+/// and replaced later.
+#[derive(Clone,Copy,Debug)]
+pub struct Fallback {
+    spacing: f64,
+    interpolation: Interpolation,
+}
+
+impl Default for Fallback {
+    fn default() -> Self {
+        Self { spacing: Self::DEFAULT_SPACING, interpolation: Interpolation::default() }
+    }
+}
+
+impl Fallback {
+    pub const DEFAULT_SPACING: f64 = 25.;
+
+    pub fn new(spacing: f64, interpolation: Interpolation) -> Self {
+        assert!(spacing > 0., "spacing must be positive");
+        Self { spacing, interpolation }
+    }
+
+    /// Points of the line from `a` to `b`, both included.
+    pub fn densify(&self, a: &Coords, b: &Coords) -> Vec<Coords> {
+        let n = (a.haversine(b) / self.spacing).ceil().max(1.) as usize;
+        let mut o = Vec::with_capacity(n + 1);
+        o.push(a.clone());
+        for i in 1..n {
+            let f = i as f64 / n as f64;
+            o.push(match self.interpolation {
+                Interpolation::Linear => a.lerp(b, f),
+                Interpolation::GreatCircle => a.slerp(b, f),
+            });
+        }
+        o.push(b.clone());
+        o
+    }
+
+    pub fn segment(&self, from: &Stop, to: &Stop) -> Segment {
+        let mut s = Segment::new_with_source(from.id, to.id, from.ty, self.densify(&from.position, &to.position), SegmentSource::Fallback);
+        s.distance = Some(from.position.haversine(&to.position));
+        s
+    }
+}
+
+#[test]
+fn fallback_test_densify() {
+    let a = Coords::new(46.0667, 11.1209);
+    let b = Coords::new(46.0767, 11.1309);
+    let d = a.haversine(&b);
+    assert!((d - 1360.).abs() < 10., "{}", d);
+
+    for i in [Interpolation::Linear, Interpolation::GreatCircle] {
+        let points = Fallback::new(100., i).densify(&a, &b);
+        assert_eq!(points.len(), 15);
+        assert_eq!(points.first(), Some(&a));
+        assert_eq!(points.last(), Some(&b));
+        assert!(points.windows(2).all(|w| w[0].haversine(&w[1]) <= 100.));
+    }
+}
@@ -0,0 +1,229 @@
+use serde::{Deserialize, Serialize};
+
+use crate::Coords;
+use super::Segment;
+
+/// Bounding box of a set of positions.
+#[derive(Serialize,Deserialize,Debug,Clone,PartialEq)]
+pub struct BBox {
+    pub min: Coords,
+    pub max: Coords,
+}
+
+impl BBox {
+    pub fn contains(&self, c: &Coords) -> bool {
+        (self.min.lat..=self.max.lat).contains(&c.lat) && (self.min.lng..=self.max.lng).contains(&c.lng)
+    }
+
+    /// Smallest box containing all the positions, `None` if there are none.
+    pub fn from_coords<'a>(coords: impl IntoIterator<Item = &'a Coords>) -> Option<Self> {
+        let mut iter = coords.into_iter();
+        let first = iter.next()?;
+        let mut o = Self { min: first.clone(), max: first.clone() };
+        for c in iter {
+            o.min.lat = o.min.lat.min(c.lat);
+            o.min.lng = o.min.lng.min(c.lng);
+            o.max.lat = o.max.lat.max(c.lat);
+            o.max.lng = o.max.lng.max(c.lng);
+        }
+        Some(o)
+    }
+
+    /// Smallest box containing both `self` and `other`.
+    pub fn union(&self, other: &Self) -> Self {
+        Self {
+            min: Coords::new(self.min.lat.min(other.min.lat), self.min.lng.min(other.min.lng)),
+            max: Coords::new(self.max.lat.max(other.max.lat), self.max.lng.max(other.max.lng)),
+        }
+    }
+}
+
+/// Equirectangular projection in meters around a reference point: accurate enough for the
+/// distances between points of a segment.
+pub(crate) struct Projection {
+    origin: Coords,
+    cos: f64,
+}
+
+impl Projection {
+    pub(crate) fn new(origin: &Coords) -> Self {
+        Self { origin: origin.clone(), cos: origin.lat.to_radians().cos() }
+    }
+
+    pub(crate) fn project(&self, c: &Coords) -> (f64, f64) {
+        (
+            (c.lng - self.origin.lng).to_radians() * Coords::EARTH_RADIUS * self.cos,
+            (c.lat - self.origin.lat).to_radians() * Coords::EARTH_RADIUS,
+        )
+    }
+}
+
+/// Fraction (from 0 to 1) of the segment `a`-`b` at which lies the point closest to `p`, in the
+/// projected plane.
+pub(crate) fn segment_fraction(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let l = dx * dx + dy * dy;
+    if l == 0. { 0. } else { (((p.0 - a.0) * dx + (p.1 - a.1) * dy) / l).clamp(0., 1.) }
+}
+
+/// Distance of `p` from the segment `a`-`b`, in the projected plane.
+fn segment_distance(p: (f64, f64), a: (f64, f64), b: (f64, f64)) -> f64 {
+    let (dx, dy) = (b.0 - a.0, b.1 - a.1);
+    let t = segment_fraction(p, a, b);
+    ((p.0 - a.0 - t * dx).powi(2) + (p.1 - a.1 - t * dy).powi(2)).sqrt()
+}
+
+fn triangle_area(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+    ((b.0 - a.0) * (c.1 - a.1) - (c.0 - a.0) * (b.1 - a.1)).abs() / 2.
+}
+
+impl Segment {
+    /// Geodesic length of the geometry, in meters.
+    pub fn length(&self) -> f64 {
+        self.geometry.windows(2).map(|w| w[0].haversine(&w[1])).sum()
+    }
+
+    pub fn bbox(&self) -> Option<BBox> {
+        BBox::from_coords(&self.geometry)
+    }
+
+    /// The same segment, walked in the opposite direction (from `to` to `from`).
+    pub fn reversed(&self) -> Self {
+        let mut o = self.clone();
+        std::mem::swap(&mut o.from, &mut o.to);
+        o.geometry.reverse();
+        o
+    }
+
+    /// Returns a copy with a geometry simplified with the Douglas-Peucker algorithm: no removed
+    /// point is farther than `tolerance` meters from the simplified line.
+    pub fn simplify(&self, tolerance: f64) -> Self {
+        self.with_geometry(self.simplify_dp(tolerance))
+    }
+
+    /// Returns a copy with a geometry simplified with the Visvalingam-Whyatt algorithm: points are
+    /// removed while the triangle they form with their neighbours is smaller than a square of side
+    /// `tolerance` meters.
+    pub fn simplify_vw(&self, tolerance: f64) -> Self {
+        self.with_geometry(self.simplify_visvalingam(tolerance * tolerance))
+    }
+
+    /// Returns a copy with a geometry resampled every `spacing` meters along the line. The first and
+    /// last points are always kept.
+    pub fn resample(&self, spacing: f64) -> Self {
+        assert!(spacing > 0., "spacing must be positive");
+        if self.geometry.len() < 2 {
+            return self.clone();
+        }
+        let mut o = vec![self.geometry[0].clone()];
+        // distance along the line to the next point to emit
+        let mut next = spacing;
+        let mut walked = 0.;
+        for w in self.geometry.windows(2) {
+            let d = w[0].haversine(&w[1]);
+            while d > 0. && next < walked + d {
+                o.push(w[0].lerp(&w[1], (next - walked) / d));
+                next += spacing;
+            }
+            walked += d;
+        }
+        let last = self.geometry.last().unwrap();
+        if o.last() != Some(last) {
+            o.push(last.clone());
+        }
+        self.with_geometry(o)
+    }
+
+    fn with_geometry(&self, geometry: Vec<Coords>) -> Self {
+        let Self { from, to, ty, distance, duration, source, .. } = *self;
+        let mut o = Self::new_with_source(from, to, ty, geometry, source);
+        o.distance = distance;
+        o.duration = duration;
+        o
+    }
+
+    fn simplify_dp(&self, tolerance: f64) -> Vec<Coords> {
+        let n = self.geometry.len();
+        if n < 3 {
+            return self.geometry.clone();
+        }
+        let proj = Projection::new(&self.geometry[0]);
+        let points = self.geometry.iter().map(|c| proj.project(c)).collect::<Vec<_>>();
+        let mut keep = vec![false; n];
+        keep[0] = true;
+        keep[n - 1] = true;
+        let mut stack = vec![(0, n - 1)];
+        while let Some((a, b)) = stack.pop() {
+            let farthest = (a + 1..b)
+                .map(|i| (i, segment_distance(points[i], points[a], points[b])))
+                .max_by(|x, y| x.1.total_cmp(&y.1));
+            if let Some((i, d)) = farthest {
+                if d > tolerance {
+                    keep[i] = true;
+                    stack.push((a, i));
+                    stack.push((i, b));
+                }
+            }
+        }
+        self.geometry.iter().zip(keep).filter(|(_, k)| *k).map(|(c, _)| c.clone()).collect()
+    }
+
+    fn simplify_visvalingam(&self, min_area: f64) -> Vec<Coords> {
+        if self.geometry.len() < 3 {
+            return self.geometry.clone();
+        }
+        let proj = Projection::new(&self.geometry[0]);
+        let points = self.geometry.iter().map(|c| proj.project(c)).collect::<Vec<_>>();
+        // indexes of the points still in the line
+        let mut line = (0..points.len()).collect::<Vec<_>>();
+        while line.len() > 2 {
+            let (i, area) = (1..line.len() - 1)
+                .map(|i| (i, triangle_area(points[line[i - 1]], points[line[i]], points[line[i + 1]])))
+                .min_by(|x, y| x.1.total_cmp(&y.1))
+                .unwrap();
+            if area >= min_area {
+                break;
+            }
+            line.remove(i);
+        }
+        line.into_iter().map(|i| self.geometry[i].clone()).collect()
+    }
+}
+
+#[test]
+fn geometry_test_segment() {
+    use tt::AreaType;
+
+    // a straight line with a small bump in the middle and a spike
+    let s = Segment::new(1, 2, AreaType::U, vec![
+        Coords::new(46.0700, 11.1200),
+        Coords::new(46.0710, 11.1200),
+        Coords::new(46.0720, 11.12001),
+        Coords::new(46.0730, 11.1200),
+        Coords::new(46.0740, 11.1230),
+        Coords::new(46.0750, 11.1200),
+    ]);
+    assert!((s.length() - 847.).abs() < 1., "{}", s.length());
+
+    let r = s.reversed();
+    assert_eq!((r.from, r.to), (2, 1));
+    assert_eq!(r.geometry[0], s.geometry[5]);
+
+    let bbox = s.bbox().unwrap();
+    assert_eq!(bbox.min, Coords::new(46.0700, 11.1200));
+    assert_eq!(bbox.max, Coords::new(46.0750, 11.1230));
+
+    for simplified in [s.simplify(5.), s.simplify_vw(15.)] {
+        assert_eq!(simplified.geometry, vec![
+            Coords::new(46.0700, 11.1200),
+            Coords::new(46.0730, 11.1200),
+            Coords::new(46.0740, 11.1230),
+            Coords::new(46.0750, 11.1200),
+        ]);
+    }
+
+    let resampled = s.resample(100.);
+    assert_eq!(resampled.geometry.first(), s.geometry.first());
+    assert_eq!(resampled.geometry.last(), s.geometry.last());
+    assert_eq!(resampled.geometry.len(), 10);
+}
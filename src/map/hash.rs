@@ -0,0 +1,109 @@
+use std::fmt::Display;
+
+use tt::AreaType;
+use sha1::Digest;
+use uuid::Uuid;
+
+use crate::Type;
+
+/// # HashVersion
+/// Version of the format used to turn a sequence of stops into a path id.
+///
+/// All versions hash the same encoding of the sequence, with different hash functions. Ids don't
+/// carry their version explicitly, but each version has a distinctive shape: `detect` uses it to
+/// find the versions an id may belong to.
+#[derive(Clone,Copy,Debug,PartialEq,Eq,Hash,PartialOrd,Ord)]
+pub enum HashVersion {
+    /// Hex encoded sha1, stop ids encoded as 16 bit big endian integers separated by `0x0`.
+    Legacy = 0,
+    /// UUIDv5 in the namespace of `Type::Path`, same stop encoding as `Legacy`.
+    V1 = 1,
+}
+
+impl HashVersion {
+    /// Version used for new paths.
+    pub const CURRENT: Self = Self::V1;
+    pub const ALL: [Self; 2] = [Self::Legacy, Self::V1];
+
+    /// Binary representation of a sequence of stops, used as input for path identifiers.
+    fn encode(ty: AreaType, seq: &[u16]) -> Vec<u8> {
+        let mut data = Vec::from([ty.into(), 0x0, 0x0]);
+        data.append(&mut seq.iter()
+            // split stop id (16 bytes) into 2 u8 (8 bytes)
+            .flat_map(|v| [(*v >> 8) as u8, (*v & 0xff) as u8])
+            // add 0x0 between each stop id (stop id == 0 doens't exists)
+            .fold(Vec::new(), |mut acc, v| {
+                acc.push(v);
+                acc.push(0x0);
+                acc
+            })
+        );
+        // remove last 0x0, only if sequence has at least 1 stop
+        if data.len() != 3 {
+            data.pop();
+        }
+        data
+    }
+
+    pub fn hash(self, ty: AreaType, seq: &[u16]) -> String {
+        let data = Self::encode(ty, seq);
+        match self {
+            Self::Legacy => {
+                let mut hasher = sha1::Sha1::new();
+                hasher.update(data);
+                format!("{:x}", hasher.finalize())
+            }
+            Self::V1 => Uuid::new_v5(&Type::Path.namespace(), &data).to_string(),
+        }
+    }
+
+    /// Returns the versions whose ids have the same shape as `id`, newest first.
+    pub fn detect(id: &str) -> Vec<Self> {
+        let mut o = Vec::new();
+        if let Ok(u) = Uuid::parse_str(id) {
+            if u.get_version_num() == 5 {
+                o.push(Self::V1);
+            }
+        } else if id.len() == 40 && id.bytes().all(|b| b.is_ascii_digit() || (b'a'..=b'f').contains(&b)) {
+            o.push(Self::Legacy);
+        }
+        o
+    }
+
+    /// Returns the version that produced `id` from the sequence, if any.
+    pub fn verify(id: &str, ty: AreaType, seq: &[u16]) -> Option<Self> {
+        Self::detect(id).into_iter().find(|v| v.hash(ty, seq) == id)
+    }
+}
+
+impl Display for HashVersion {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Legacy => write!(f, "legacy"),
+            Self::V1 => write!(f, "v1"),
+        }
+    }
+}
+
+/// Legacy path identifier: hex encoded sha1 of the sequence.
+/// Use `sequence_uuid` for new paths.
+pub fn sequence_hash(ty: AreaType, seq: &[u16]) -> String {
+    HashVersion::Legacy.hash(ty, seq)
+}
+
+/// Path identifier, in the current version.
+pub fn sequence_uuid(ty: AreaType, seq: &[u16]) -> Uuid {
+    Uuid::parse_str(&HashVersion::CURRENT.hash(ty, seq)).expect("the current hash version yields UUIDs")
+}
+
+#[test]
+fn hash_test_v1_stable() {
+    // V1 ids are the UUIDv5 of the sequence with a `[ty, 0, 0]` header: changing it changes every id
+    let ty = AreaType::U;
+    // every byte of the stop ids is followed by `0x0`, except the last one
+    let data: [u8; 14] = [ty.into(), 0x0, 0x0, 0x0, 0x0, 0x1, 0x0, 0x0, 0x0, 0x2, 0x0, 0x1, 0x0, 0x0];
+    let stored = Uuid::new_v5(&Type::Path.namespace(), &data).to_string();
+    assert_eq!(HashVersion::V1.hash(ty, &[1, 2, 256]), stored);
+    assert_eq!(sequence_uuid(ty, &[1, 2, 256]).to_string(), stored);
+    assert_eq!(HashVersion::verify(&stored, ty, &[1, 2, 256]), Some(HashVersion::V1));
+}
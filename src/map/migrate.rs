@@ -0,0 +1,91 @@
+use std::collections::HashMap;
+
+use crate::Trip;
+use super::{HashVersion, Path};
+
+/// # PathIdMigration
+/// Maps the identifiers of paths generated with an older `HashVersion` (like the legacy
+/// `sequence_hash`) to the current one.
+///
+/// Build it from the stored paths, then use it to rewrite the ids of the paths themselves and
+/// the references held by trips.
+#[derive(Debug, Default)]
+pub struct PathIdMigration {
+    ids: HashMap<String, String>,
+}
+
+impl PathIdMigration {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Registers the mapping for `path`, whose id can be in any version.
+    pub fn insert(&mut self, path: &Path) {
+        let new = HashVersion::CURRENT.hash(path.ty, &path.sequence);
+        for v in HashVersion::ALL.into_iter().filter(|v| *v != HashVersion::CURRENT) {
+            self.ids.insert(v.hash(path.ty, &path.sequence), new.clone());
+        }
+    }
+
+    /// Returns the new id of the path with the old id `old`.
+    pub fn get(&self, old: &str) -> Option<&str> {
+        self.ids.get(old).map(|s| s.as_str())
+    }
+
+    /// Rewrites the id of `path`, returning `true` if it has been changed.
+    pub fn migrate_path(&self, path: &mut Path) -> bool {
+        Self::migrate(&self.ids, &mut path.id)
+    }
+
+    /// Rewrites the path referenced by `trip`, returning `true` if it has been changed.
+    /// Trips referencing unknown paths are left untouched.
+    pub fn migrate_trip(&self, trip: &mut Trip) -> bool {
+        Self::migrate(&self.ids, &mut trip.path)
+    }
+
+    fn migrate(ids: &HashMap<String, String>, id: &mut String) -> bool {
+        match ids.get(id.as_str()) {
+            Some(new) if new != id => {
+                *id = new.clone();
+                true
+            }
+            _ => false
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.ids.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.ids.is_empty()
+    }
+}
+
+impl<'a> FromIterator<&'a Path> for PathIdMigration {
+    fn from_iter<I: IntoIterator<Item = &'a Path>>(iter: I) -> Self {
+        let mut o = Self::new();
+        for p in iter.into_iter() {
+            o.insert(p);
+        }
+        o
+    }
+}
+
+#[test]
+fn path_id_migration_test() {
+    use tt::AreaType;
+    use crate::RoutingType;
+
+    let new = Path::new(vec![4, 8, 15, 16], AreaType::U, RoutingType::Bus);
+    let mut old = Path::new(vec![4, 8, 15, 16], AreaType::U, RoutingType::Bus);
+    old.id = old.legacy_id();
+    assert_eq!(old.id.len(), 40);
+    assert_eq!(new.id.len(), 36);
+
+    let m = PathIdMigration::from_iter([&old]);
+    assert_eq!(m.get(&old.id), Some(new.id.as_str()));
+    assert!(m.migrate_path(&mut old));
+    assert_eq!(old.id, new.id);
+    assert!(!m.migrate_path(&mut old));
+}
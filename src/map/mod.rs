@@ -1,31 +1,24 @@
 mod segment;
 mod path;
+mod hash;
+mod migrate;
+mod registry;
+mod fallback;
+mod geometry;
+mod validate;
 #[cfg(feature = "polyline")]
 pub mod polyline;
+#[cfg(feature = "osrm")]
+pub mod osrm;
+#[cfg(feature = "railway")]
+pub mod railway;
 
 pub use path::{RoutingType,Path};
-pub use segment::Segment;
-
-use tt::AreaType;
-use sha1::Digest;
-
-pub fn sequence_hash(ty: AreaType, seq: &[u16]) -> String {
-    let mut data = Vec::from([ty.into(), 0x0, 0x0]);
-    data.append(&mut seq.iter()
-        // split stop id (16 bytes) into 2 u8 (8 bytes)
-        .flat_map(|v| [(*v >> 8) as u8, (*v & 0xff) as u8])
-        // add 0x0 between each stop id (stop id == 0 doens't exists)
-        .fold(Vec::new(), |mut acc, v| {
-            acc.push(v);
-            acc.push(0x0);
-            acc
-        })
-    );
-    // remove last 0x0, only if sequence has at least 1 stop
-    if data.len() != 3 {
-        data.pop();
-    }
-    let mut hasher = sha1::Sha1::new();
-    hasher.update(data);
-    format!("{:x}", hasher.finalize())
-}
+pub(crate) use path::{common_subsequence,merge_ordered,align};
+pub use segment::{Segment,SegmentSource};
+pub use fallback::{Fallback,Interpolation};
+pub use geometry::BBox;
+pub use validate::{SegmentValidator,SegmentReport,Thresholds,Issue};
+pub use hash::{HashVersion,sequence_hash,sequence_uuid};
+pub use migrate::PathIdMigration;
+pub use registry::{PathRegistry,PathCollision,StalePath,IngestError};
@@ -0,0 +1,211 @@
+//! Requests to an [OSRM](https://project-osrm.org/) server and parsing of its responses into
+//! `Segment`s.
+//!
+//! This module doesn't perform any network request: it builds the request urls and parses the
+//! response bodies, so that any http client can be used.
+
+use std::{collections::HashMap, fmt::Display};
+
+use serde::Deserialize;
+use tt::AreaType;
+
+use crate::Coords;
+use super::{RoutingType, Segment};
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum Service {
+    /// Shortest route through the given coordinates, in order.
+    Route,
+    /// Snap the given coordinates to the network, as if they were a gps trace.
+    Match,
+}
+
+impl Service {
+    fn as_str(&self) -> &'static str {
+        match self {
+            Self::Route => "route",
+            Self::Match => "match",
+        }
+    }
+}
+
+#[derive(Debug)]
+pub enum OsrmError {
+    /// No profile has been configured for the routing type.
+    Unroutable(RoutingType),
+    /// A request needs at least two coordinates.
+    TooFewCoords(usize),
+    Json(serde_json::Error),
+    /// The server answered with an error code, like `NoRoute` or `NoMatch`.
+    Server { code: String, message: Option<String> },
+    /// The response doesn't have one leg for each pair of consecutive stops.
+    Legs { expected: usize, found: usize },
+}
+
+impl std::error::Error for OsrmError {}
+
+impl Display for OsrmError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Unroutable(r) => write!(f, "no osrm profile for routing type {:?}", r),
+            Self::TooFewCoords(n) => write!(f, "at least 2 coordinates are needed, got {}", n),
+            Self::Json(e) => write!(f, "invalid osrm response: {}", e),
+            Self::Server { code, message } => write!(f, "osrm error {}: {}", code, message.as_deref().unwrap_or("")),
+            Self::Legs { expected, found } => write!(f, "expected {} legs, found {}", expected, found),
+        }
+    }
+}
+
+impl From<serde_json::Error> for OsrmError {
+    fn from(value: serde_json::Error) -> Self {
+        Self::Json(value)
+    }
+}
+
+/// # Osrm
+/// Builds requests for an osrm server, with one profile for each `RoutingType`.
+///
+/// By default buses use the `driving` profile and the other routing types have no profile, since
+/// a road network can't route them: use `profile` to set up a custom one (e.g. a profile built from
+/// railway data).
+#[derive(Debug, Clone)]
+pub struct Osrm {
+    base: String,
+    profiles: HashMap<RoutingType, String>,
+}
+
+impl Osrm {
+    pub fn new(base: impl Into<String>) -> Self {
+        let base = base.into().trim_end_matches('/').to_string();
+        Self { base, profiles: HashMap::from([(RoutingType::Bus, "driving".to_string())]) }
+    }
+
+    pub fn profile(mut self, rty: RoutingType, profile: impl Into<String>) -> Self {
+        self.profiles.insert(rty, profile.into());
+        self
+    }
+
+    /// Url of a `route` request through `coords`, with one leg for each pair of consecutive
+    /// coordinates.
+    pub fn route(&self, rty: RoutingType, coords: &[Coords]) -> Result<String, OsrmError> {
+        self.request(Service::Route, rty, coords, "continue_straight=true")
+    }
+
+    /// Url of a `match` request on `coords`. Gaps are ignored and the trace isn't tidied, so that
+    /// each coordinate produces a tracepoint.
+    pub fn matching(&self, rty: RoutingType, coords: &[Coords]) -> Result<String, OsrmError> {
+        self.request(Service::Match, rty, coords, "gaps=ignore&tidy=false")
+    }
+
+    fn request(&self, service: Service, rty: RoutingType, coords: &[Coords], extra: &str) -> Result<String, OsrmError> {
+        let profile = self.profiles.get(&rty).ok_or(OsrmError::Unroutable(rty))?;
+        if coords.len() < 2 {
+            return Err(OsrmError::TooFewCoords(coords.len()));
+        }
+        let coords = coords.iter().map(Coords::to_osrm_query).collect::<Vec<_>>().join(";");
+        Ok(format!(
+            "{}/{}/v1/{}/{}?overview=false&steps=true&geometries=geojson&{}",
+            self.base, service.as_str(), profile, coords, extra,
+        ))
+    }
+}
+
+#[derive(Deserialize)]
+struct Response {
+    code: String,
+    message: Option<String>,
+    #[serde(default)]
+    routes: Vec<Route>,
+    #[serde(default)]
+    matchings: Vec<Route>,
+}
+
+#[derive(Deserialize)]
+struct Route {
+    legs: Vec<Leg>,
+}
+
+#[derive(Deserialize)]
+struct Leg {
+    distance: f64,
+    duration: f64,
+    steps: Vec<Step>,
+}
+
+#[derive(Deserialize)]
+struct Step {
+    geometry: Geometry,
+}
+
+#[derive(Deserialize)]
+struct Geometry {
+    /// `[lng, lat]` pairs
+    coordinates: Vec<[f64; 2]>,
+}
+
+impl Leg {
+    fn into_segment(self, ty: AreaType, from: u16, to: u16) -> Segment {
+        let mut geometry: Vec<Coords> = Vec::new();
+        for [lng, lat] in self.steps.into_iter().flat_map(|s| s.geometry.coordinates) {
+            let c = Coords::new(lat, lng);
+            // the last point of a step is the first of the next one
+            if geometry.last() != Some(&c) {
+                geometry.push(c);
+            }
+        }
+        let mut s = Segment::new(from, to, ty, geometry);
+        s.distance = Some(self.distance);
+        s.duration = Some(self.duration);
+        s
+    }
+}
+
+/// Parses the response of a `route` or `match` request made through the stops of `sequence`,
+/// returning a `Segment` for each pair of consecutive stops.
+pub fn parse_segments(body: &str, ty: AreaType, sequence: &[u16]) -> Result<Vec<Segment>, OsrmError> {
+    let Response { code, message, routes, matchings } = serde_json::from_str(body)?;
+    if code != "Ok" {
+        return Err(OsrmError::Server { code, message });
+    }
+    // a match request can be split in many matchings if some points can't be matched, but since
+    // we ask to ignore gaps there should be only one.
+    let legs = routes.into_iter()
+        .next()
+        .map(|r| r.legs)
+        .unwrap_or_else(|| matchings.into_iter().flat_map(|m| m.legs).collect());
+    let expected = sequence.len().saturating_sub(1);
+    if legs.len() != expected {
+        return Err(OsrmError::Legs { expected, found: legs.len() });
+    }
+    Ok(legs.into_iter()
+        .zip(sequence.windows(2))
+        .map(|(l, w)| l.into_segment(ty, w[0], w[1]))
+        .collect())
+}
+
+#[test]
+fn osrm_test_route() {
+    let osrm = Osrm::new("http://localhost:5000/");
+    let coords = [Coords::new(46.0667, 11.1209), Coords::new(46.0701, 11.1242), Coords::new(46.0723, 11.1198)];
+    assert_eq!(
+        osrm.route(RoutingType::Bus, &coords).unwrap(),
+        "http://localhost:5000/route/v1/driving/11.1209,46.0667;11.1242,46.0701;11.1198,46.0723?overview=false&steps=true&geometries=geojson&continue_straight=true",
+    );
+    assert!(matches!(osrm.route(RoutingType::Cableway, &coords), Err(OsrmError::Unroutable(_))));
+
+    let body = include_str!(concat!(env!("CARGO_MANIFEST_DIR"), "/tests/fixtures/osrm_route.json"));
+    let segments = parse_segments(body, AreaType::U, &[10, 20, 30]).unwrap();
+    assert_eq!(segments.len(), 2);
+    assert_eq!((segments[0].from, segments[0].to), (10, 20));
+    assert_eq!(segments[0].distance, Some(512.3));
+    assert_eq!(segments[1].duration, Some(61.4));
+    assert_eq!(segments[0].geometry.len(), 4);
+    assert_eq!(segments[0].geometry[0], Coords::new(46.0667, 11.1209));
+    assert_eq!(segments[0].geometry.last(), segments[1].geometry.first());
+
+    assert!(matches!(parse_segments(body, AreaType::U, &[10, 20]), Err(OsrmError::Legs { expected: 1, found: 2 })));
+    assert!(matches!(
+        parse_segments(r#"{"code":"NoRoute","message":"Impossible route"}"#, AreaType::U, &[10, 20]),
+        Err(OsrmError::Server { .. }),
+    ));
+}
@@ -1,8 +1,9 @@
 use serde::{Serialize,Deserialize};
 use tt::AreaType;
+use uuid::Uuid;
 
 use crate::{Type, BrussType, Route, StopPair};
-use super::sequence_hash;
+use super::{sequence_hash, sequence_uuid, HashVersion};
 
 /// # Path
 /// It holds data about the path that a trip follows.
@@ -13,7 +14,9 @@ use super::sequence_hash;
 /// holiday or something.
 ///
 /// `id` is generated by bruss, based on the path area type the path belongs to and the sequence
-/// of stops that the path follows.
+/// of stops that the path follows: it's a UUIDv5 in the namespace of `Type::Path` (see
+/// `sequence_uuid`). Paths stored before the introduction of UUIDs have a `sequence_hash` id, that
+/// can be migrated with `PathIdMigration`.
 /// `rty` is an internal information: it is used by the router to determine the type of routing it
 /// has to perform, for joining the stops and creating segments.
 #[derive(Serialize,Deserialize,Debug)]
@@ -43,7 +46,22 @@ impl From<&'_ Route> for RoutingType {
 
 impl Path {
     pub fn new(sequence: Vec<u16>, ty: AreaType, rty: RoutingType) -> Self {
-        Self { id: sequence_hash(ty, &sequence), sequence, ty, rty }
+        Self { id: sequence_uuid(ty, &sequence).to_string(), sequence, ty, rty }
+    }
+
+    pub fn uuid(&self) -> Uuid {
+        sequence_uuid(self.ty, &self.sequence)
+    }
+
+    /// Id that this path would have had before the introduction of UUIDs.
+    pub fn legacy_id(&self) -> String {
+        sequence_hash(self.ty, &self.sequence)
+    }
+
+    /// Checks `id` against the sequence, returning the version of the format it has been
+    /// generated with, or `None` if it doesn't match the sequence in any known version.
+    pub fn verify_id(&self) -> Option<HashVersion> {
+        HashVersion::verify(&self.id, self.ty, &self.sequence)
     }
 
     pub fn segments_to_sequence(segments: Vec<StopPair>) -> Vec<u16> {
@@ -70,6 +88,18 @@ impl Path {
         Self::new(Self::segments_to_sequence(segments), ty, routing_ty)
     }
 
+    /// Merges stop sequences into one that contains all of them, in order: every sequence is
+    /// aligned to the ones merged before it on their longest common subsequence, and its other
+    /// stops are inserted where they fall. Longer sequences are merged first, so the longest one is
+    /// never reordered. A stop can appear more than once, if the sequences disagree on its
+    /// position or if a sequence passes twice through it.
+    pub fn merge_sequences<'a>(sequences: impl IntoIterator<Item = &'a [u16]>) -> Vec<u16> {
+        let mut sequences = sequences.into_iter().collect::<Vec<_>>();
+        sequences.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        sequences.dedup();
+        merge_ordered(sequences)
+    }
+
     pub fn segments(&self) -> Vec<StopPair> {
         let mut o = Vec::with_capacity(self.sequence.len() + 1);
         let mut p = self.sequence[0];
@@ -81,6 +111,64 @@ impl Path {
     }
 }
 
+/// Positions in `a` and in `b` of the stops of a longest common subsequence of the two.
+pub(crate) fn common_subsequence(a: &[u16], b: &[u16]) -> Vec<(usize, usize)> {
+    // lcs[i][j] is the length of the longest common subsequence of a[i..] and b[j..]
+    let mut lcs = vec![vec![0u32; b.len() + 1]; a.len() + 1];
+    for i in (0..a.len()).rev() {
+        for j in (0..b.len()).rev() {
+            lcs[i][j] = if a[i] == b[j] { lcs[i + 1][j + 1] + 1 } else { lcs[i + 1][j].max(lcs[i][j + 1]) };
+        }
+    }
+    let (mut i, mut j) = (0, 0);
+    let mut o = Vec::new();
+    while i < a.len() && j < b.len() {
+        if a[i] == b[j] {
+            o.push((i, j));
+            (i, j) = (i + 1, j + 1);
+        } else if lcs[i + 1][j] >= lcs[i][j + 1] {
+            i += 1;
+        } else {
+            j += 1;
+        }
+    }
+    o
+}
+
+/// Like `Path::merge_sequences`, but merges the sequences in the given order, so that the first
+/// one is never reordered.
+pub(crate) fn merge_ordered<'a>(sequences: impl IntoIterator<Item = &'a [u16]>) -> Vec<u16> {
+    sequences.into_iter().fold(Vec::new(), |o, s| merge_two(&o, s))
+}
+
+/// Shortest sequence containing both `a` and `b`, stops of `a` first where they differ.
+fn merge_two(a: &[u16], b: &[u16]) -> Vec<u16> {
+    let (mut i, mut j) = (0, 0);
+    let mut o = Vec::with_capacity(a.len() + b.len());
+    for (x, y) in common_subsequence(a, b) {
+        o.extend_from_slice(&a[i..x]);
+        o.extend_from_slice(&b[j..y]);
+        o.push(a[x]);
+        (i, j) = (x + 1, y + 1);
+    }
+    o.extend_from_slice(&a[i..]);
+    o.extend_from_slice(&b[j..]);
+    o
+}
+
+/// Positions in `merged` of the stops of `sequence`, one of the sequences it has been merged from
+/// with `Path::merge_sequences`.
+pub(crate) fn align(merged: &[u16], sequence: &[u16]) -> Vec<usize> {
+    let mut o = Vec::with_capacity(sequence.len());
+    let mut rows = merged.iter().enumerate();
+    for s in sequence {
+        if let Some((r, _)) = rows.by_ref().find(|(_, m)| *m == s) {
+            o.push(r);
+        }
+    }
+    o
+}
+
 impl PartialEq for Path {
     fn eq(&self, other: &Self) -> bool {
         self.id == other.id || self.sequence == other.sequence
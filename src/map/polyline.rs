@@ -1,5 +1,5 @@
 use super::Segment;
-use super::AreaType;
+use tt::AreaType;
 use serde::Serialize;
 use polyline::encode_coordinates;
 use geo_types::LineString;
@@ -15,7 +15,7 @@ pub struct PolySegment {
 
 impl From<Segment> for PolySegment {
     fn from(value: Segment) -> Self {
-        let Segment { from, to, ty, geometry: coords } = value;
+        let Segment { from, to, ty, geometry: coords, .. } = value;
         let polyline = encode_coordinates::<LineString>(LineString::from_iter(coords.iter().map(|c| (c.lat, c.lng))), 5).unwrap();
         Self { from, to, ty, geometry: polyline }
     }
@@ -0,0 +1,312 @@
+//! Geometry of railway segments, computed offline from an OpenStreetMap extract.
+
+use std::{collections::{BinaryHeap, HashMap, HashSet}, fmt::Display, path::Path as FsPath};
+
+use osmpbf::{Element, ElementReader};
+
+use crate::{helpers::MinCost, Coords, Stop};
+use super::{geometry::{segment_fraction, Projection}, Segment, SegmentSource};
+
+/// Values of the `railway` tag of the ways that are part of the graph.
+const RAILWAY_TAGS: [&str; 3] = ["rail", "narrow_gauge", "light_rail"];
+/// Size (in degrees) of the cells of the grid used for snapping.
+const CELL: f64 = 0.01;
+
+#[derive(Debug)]
+pub enum RailError {
+    Pbf(osmpbf::Error),
+    /// No railway is close enough to the stop.
+    NotSnapped(u16),
+    /// The two stops are on disconnected parts of the network.
+    NoPath(u16, u16),
+}
+
+impl std::error::Error for RailError {}
+
+impl Display for RailError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Pbf(e) => write!(f, "cannot read osm extract: {}", e),
+            Self::NotSnapped(s) => write!(f, "stop {} is too far from the railway network", s),
+            Self::NoPath(a, b) => write!(f, "no railway path from stop {} to stop {}", a, b),
+        }
+    }
+}
+
+impl From<osmpbf::Error> for RailError {
+    fn from(value: osmpbf::Error) -> Self {
+        Self::Pbf(value)
+    }
+}
+
+/// The point of the railway network closest to a position.
+#[derive(Debug, Clone)]
+pub struct Snap {
+    /// Index of the edge the point is on.
+    pub edge: usize,
+    /// Fraction of the edge (from 0 to 1) from its first node to the point.
+    pub fraction: f64,
+    pub position: Coords,
+    /// Distance in meters from the snapped position.
+    pub distance: f64,
+}
+
+/// # RailGraph
+/// Undirected graph of the railway network: nodes are osm nodes, edges join consecutive nodes of
+/// railway ways and are weighted by their length in meters.
+///
+/// Stops are snapped to the closest point of an edge, not to the closest node: on straight track
+/// nodes can be kilometers apart.
+pub struct RailGraph {
+    nodes: Vec<Coords>,
+    adjacency: Vec<Vec<(usize, f64)>>,
+    /// Both nodes and length of each edge.
+    edges: Vec<(usize, usize, f64)>,
+    /// Edges crossing each cell.
+    grid: HashMap<(i32, i32), Vec<usize>>,
+    max_snap: f64,
+}
+
+impl RailGraph {
+    pub const DEFAULT_MAX_SNAP: f64 = 200.;
+
+    /// Loads the railway ways of a `.osm.pbf` extract.
+    /// The file is read twice: first for the ways, then for the coordinates of their nodes.
+    pub fn from_pbf(path: impl AsRef<FsPath>) -> Result<Self, RailError> {
+        let mut ways = Vec::new();
+        ElementReader::from_path(path.as_ref())?.for_each(|e| {
+            if let Element::Way(w) = e {
+                if w.tags().any(|(k, v)| k == "railway" && RAILWAY_TAGS.contains(&v)) {
+                    ways.push(w.refs().collect::<Vec<i64>>());
+                }
+            }
+        })?;
+        let needed = ways.iter().flatten().copied().collect::<HashSet<i64>>();
+        let mut nodes = HashMap::with_capacity(needed.len());
+        ElementReader::from_path(path.as_ref())?.for_each(|e| {
+            let (id, lat, lng) = match e {
+                Element::Node(n) => (n.id(), n.lat(), n.lon()),
+                Element::DenseNode(n) => (n.id(), n.lat(), n.lon()),
+                _ => return,
+            };
+            if needed.contains(&id) {
+                nodes.insert(id, Coords::new(lat, lng));
+            }
+        })?;
+        Ok(Self::from_ways(&nodes, &ways))
+    }
+
+    /// Builds the graph from the coordinates of the nodes and the ways, as lists of node ids.
+    /// Nodes without coordinates (e.g. cut out of the extract) are skipped.
+    pub fn from_ways(coords: &HashMap<i64, Coords>, ways: &[Vec<i64>]) -> Self {
+        let mut index = HashMap::new();
+        let mut nodes = Vec::new();
+        let mut adjacency: Vec<Vec<(usize, f64)>> = Vec::new();
+        let mut edges = Vec::new();
+        for w in ways {
+            let mut prev: Option<usize> = None;
+            for id in w {
+                let Some(c) = coords.get(id) else { continue };
+                let i = *index.entry(*id).or_insert_with(|| {
+                    nodes.push(c.clone());
+                    adjacency.push(Vec::new());
+                    nodes.len() - 1
+                });
+                if let Some(p) = prev {
+                    let d = nodes[p].haversine(&nodes[i]);
+                    adjacency[p].push((i, d));
+                    adjacency[i].push((p, d));
+                    edges.push((p, i, d));
+                }
+                prev = Some(i);
+            }
+        }
+        let mut grid: HashMap<(i32, i32), Vec<usize>> = HashMap::new();
+        for (e, (a, b, _)) in edges.iter().enumerate() {
+            let (a, b) = (&nodes[*a], &nodes[*b]);
+            // sample the edge every half cell, so that no crossed cell is skipped
+            let steps = ((a.lat - b.lat).abs().max((a.lng - b.lng).abs()) / CELL * 2.).ceil() as usize;
+            for k in 0..=steps {
+                let crossing = grid.entry(cell(&a.lerp(b, k as f64 / steps.max(1) as f64))).or_default();
+                if crossing.last() != Some(&e) {
+                    crossing.push(e);
+                }
+            }
+        }
+        Self { nodes, adjacency, edges, grid, max_snap: Self::DEFAULT_MAX_SNAP }
+    }
+
+    /// Maximum distance in meters between a stop and the point it's snapped to.
+    pub fn max_snap(mut self, meters: f64) -> Self {
+        self.max_snap = meters;
+        self
+    }
+
+    /// Returns the point of the network closest to `c`, if within `max_snap`.
+    pub fn snap(&self, c: &Coords) -> Option<Snap> {
+        let (x, y) = cell(c);
+        // a cell is at least ~700m wide at our latitudes, so neighbouring cells are enough unless
+        // `max_snap` is very large. One more ring makes up for the sampling of the edges.
+        let r = (self.max_snap / 700.).ceil() as i32 + 1;
+        let projection = Projection::new(c);
+        (x - r..=x + r)
+            .flat_map(|i| (y - r..=y + r).map(move |j| (i, j)))
+            .filter_map(|k| self.grid.get(&k))
+            .flatten()
+            .map(|e| {
+                let (a, b) = (&self.nodes[self.edges[*e].0], &self.nodes[self.edges[*e].1]);
+                let fraction = segment_fraction((0., 0.), projection.project(a), projection.project(b));
+                let position = a.lerp(b, fraction);
+                Snap { edge: *e, fraction, distance: position.haversine(c), position }
+            })
+            .filter(|s| s.distance <= self.max_snap)
+            .min_by(|a, b| a.distance.total_cmp(&b.distance))
+    }
+
+    /// Shortest path from node `a` to node `b`, with its length in meters.
+    pub fn shortest_path(&self, a: usize, b: usize) -> Option<(Vec<usize>, f64)> {
+        self.dijkstra(&[(a, 0.)], &[(b, 0.)])
+    }
+
+    /// Shortest path between two snapped points, as if their edges were split there: the nodes
+    /// in between (none if they're on the same edge), with the length in meters.
+    pub fn shortest_path_between(&self, a: &Snap, b: &Snap) -> Option<(Vec<usize>, f64)> {
+        let (u, v, length) = self.edges[a.edge];
+        if a.edge == b.edge {
+            return Some((Vec::new(), (b.fraction - a.fraction).abs() * length));
+        }
+        let (x, y, other) = self.edges[b.edge];
+        self.dijkstra(
+            &[(u, a.fraction * length), (v, (1. - a.fraction) * length)],
+            &[(x, b.fraction * other), (y, (1. - b.fraction) * other)],
+        )
+    }
+
+    /// Shortest path from any of the `sources` to any of the `targets`, each with the cost of
+    /// starting or ending there.
+    fn dijkstra(&self, sources: &[(usize, f64)], targets: &[(usize, f64)]) -> Option<(Vec<usize>, f64)> {
+        let mut dist = vec![f64::INFINITY; self.nodes.len()];
+        let mut prev = vec![usize::MAX; self.nodes.len()];
+        let mut heap = BinaryHeap::new();
+        for (s, c) in sources {
+            if *c < dist[*s] {
+                dist[*s] = *c;
+                heap.push(MinCost { cost: *c, node: *s });
+            }
+        }
+        let mut best: Option<(usize, f64)> = None;
+        while let Some(MinCost { cost, node }) = heap.pop() {
+            if best.is_some_and(|(_, b)| cost >= b) {
+                break;
+            }
+            if cost > dist[node] {
+                continue;
+            }
+            for (_, extra) in targets.iter().filter(|(t, _)| *t == node) {
+                if !best.is_some_and(|(_, b)| cost + extra >= b) {
+                    best = Some((node, cost + extra));
+                }
+            }
+            for (next, w) in &self.adjacency[node] {
+                let c = cost + w;
+                if c < dist[*next] {
+                    dist[*next] = c;
+                    prev[*next] = node;
+                    heap.push(MinCost { cost: c, node: *next });
+                }
+            }
+        }
+        let (end, cost) = best?;
+        let mut path = vec![end];
+        while prev[*path.last().unwrap()] != usize::MAX {
+            path.push(prev[*path.last().unwrap()]);
+        }
+        path.reverse();
+        Some((path, cost))
+    }
+
+    /// Segment from `from` to `to` following the tracks. The geometry starts and ends at the
+    /// position of the stops.
+    pub fn segment(&self, from: &Stop, to: &Stop) -> Result<Segment, RailError> {
+        let a = self.snap(&from.position).ok_or(RailError::NotSnapped(from.id))?;
+        let b = self.snap(&to.position).ok_or(RailError::NotSnapped(to.id))?;
+        let (path, length) = self.shortest_path_between(&a, &b).ok_or(RailError::NoPath(from.id, to.id))?;
+        let mut geometry: Vec<Coords> = Vec::with_capacity(path.len() + 4);
+        let points = [&from.position, &a.position].into_iter()
+            .chain(path.iter().map(|n| &self.nodes[*n]))
+            .chain([&b.position, &to.position]);
+        for c in points {
+            if geometry.last() != Some(c) {
+                geometry.push(c.clone());
+            }
+        }
+        let mut s = Segment::new_with_source(from.id, to.id, from.ty, geometry, SegmentSource::Routed);
+        s.distance = Some(a.distance + length + b.distance);
+        Ok(s)
+    }
+
+    pub fn len(&self) -> usize {
+        self.nodes.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.nodes.is_empty()
+    }
+}
+
+fn cell(c: &Coords) -> (i32, i32) {
+    ((c.lat / CELL).floor() as i32, (c.lng / CELL).floor() as i32)
+}
+
+#[test]
+fn railway_test_segment() {
+    use tt::AreaType;
+
+    // a line with a branch: 1 - 2 - 3 - 4 and 2 - 5
+    let coords = HashMap::from([
+        (1, Coords::new(46.0700, 11.1200)),
+        (2, Coords::new(46.0710, 11.1210)),
+        (3, Coords::new(46.0720, 11.1220)),
+        (4, Coords::new(46.0730, 11.1230)),
+        (5, Coords::new(46.0700, 11.1240)),
+    ]);
+    let g = RailGraph::from_ways(&coords, &[vec![1, 2, 3], vec![3, 4], vec![2, 5]]);
+    assert_eq!(g.len(), 5);
+
+    let stop = |id, position| crate::test_util::stop(AreaType::E, id, "", position);
+    let a = stop(10, Coords::new(46.07001, 11.12001));
+    let b = stop(20, Coords::new(46.0730, 11.1230));
+    let s = g.segment(&a, &b).unwrap();
+    assert_eq!(s.geometry[0], a.position);
+    assert!(s.geometry.contains(&coords[&2]) && s.geometry.contains(&coords[&3]));
+    assert!(!s.geometry.contains(&coords[&5]));
+    assert_eq!(s.geometry.last(), Some(&b.position));
+
+    let far = stop(30, Coords::new(46.2, 11.3));
+    assert!(matches!(g.segment(&a, &far), Err(RailError::NotSnapped(30))));
+}
+
+#[test]
+fn railway_test_snap_between_nodes() {
+    use tt::AreaType;
+
+    // straight track with nodes about 5.5km apart
+    let coords = HashMap::from([(1, Coords::new(46.00, 11.10)), (2, Coords::new(46.05, 11.10)), (3, Coords::new(46.06, 11.11))]);
+    let g = RailGraph::from_ways(&coords, &[vec![1, 2, 3]]);
+
+    let stop = |id, position| crate::test_util::stop(AreaType::E, id, "", position);
+    let a = stop(10, Coords::new(46.02, 11.1005));
+    let b = stop(20, Coords::new(46.04, 11.0995));
+    let snap = g.snap(&a.position).unwrap();
+    assert_eq!(snap.edge, 0);
+    assert!((snap.fraction - 0.4).abs() < 1e-6 && (snap.distance - 38.7).abs() < 1., "{:?}", snap);
+
+    let s = g.segment(&a, &b).unwrap();
+    assert_eq!(s.geometry.len(), 4);
+    assert!((s.distance.unwrap() - (2224. + 2. * 38.7)).abs() < 5., "{:?}", s.distance);
+
+    // the second stop is past node 2
+    let c = stop(30, Coords::new(46.055, 11.105));
+    let s = g.segment(&a, &c).unwrap();
+    assert!(s.geometry.contains(&coords[&2]));
+}
@@ -0,0 +1,281 @@
+use std::{collections::{HashMap, HashSet}, fmt::Display};
+
+use chrono::TimeDelta;
+use tt::{AreaType, TTTrip};
+
+use crate::{Route, StopPair, Trip, TripError};
+use super::{HashVersion, Path, Segment};
+
+/// Two different sequences that map to the same path id.
+#[derive(Debug)]
+pub struct PathCollision {
+    pub id: String,
+    pub existing: (AreaType, Vec<u16>),
+    pub colliding: (AreaType, Vec<u16>),
+}
+
+impl std::error::Error for PathCollision {}
+
+impl Display for PathCollision {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "path id {} is shared by {:?} and {:?}", self.id, self.existing, self.colliding)
+    }
+}
+
+#[derive(Debug)]
+pub enum IngestError {
+    /// The trip references a route that isn't known to the caller.
+    UnknownRoute { trip: String, route: u16 },
+    /// The route of the trip has a type that doesn't map to a `RoutingType`.
+    UnknownRouteType { route: u16, ty: u16 },
+    Trip(TripError),
+    Collision(PathCollision),
+}
+
+impl std::error::Error for IngestError {}
+
+impl Display for IngestError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::UnknownRoute { trip, route } => write!(f, "trip {} references unknown route {}", trip, route),
+            Self::UnknownRouteType { route, ty } => write!(f, "route {} has unrecognized type {}", route, ty),
+            Self::Trip(e) => write!(f, "{}", e),
+            Self::Collision(c) => write!(f, "{}", c),
+        }
+    }
+}
+
+impl From<TripError> for IngestError {
+    fn from(value: TripError) -> Self {
+        Self::Trip(value)
+    }
+}
+
+impl From<PathCollision> for IngestError {
+    fn from(value: PathCollision) -> Self {
+        Self::Collision(value)
+    }
+}
+
+/// A stored path whose id doesn't match its sequence in the current `HashVersion`.
+#[derive(Debug)]
+pub struct StalePath {
+    pub id: String,
+    /// Id of the path in the current version.
+    pub expected: String,
+    /// Version the stored id has been generated with, `None` if the id doesn't match the sequence
+    /// in any known version (e.g. the sequence has been modified after the id was generated).
+    pub version: Option<HashVersion>,
+}
+
+/// # PathRegistry
+/// Collection of unique paths, indexed by id.
+///
+/// Inserting a path whose id is already taken by a different sequence is an error, instead of
+/// silently overwriting (or merging) the two paths.
+///
+/// Trips converted through `ingest` are tracked as well, so that the registry knows which path
+/// each trip follows and how many trips use each path.
+#[derive(Debug, Default)]
+pub struct PathRegistry {
+    paths: HashMap<String, Path>,
+    stale: Vec<StalePath>,
+    /// trip id => path id
+    trips: HashMap<String, String>,
+    /// path id => number of trips
+    usage: HashMap<String, usize>,
+}
+
+impl PathRegistry {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Inserts `path`, returning the registered path with the same id: if the sequence is
+    /// already known `path` is discarded.
+    pub fn insert(&mut self, path: Path) -> Result<&Path, PathCollision> {
+        if let Some(existing) = self.paths.get(&path.id) {
+            if existing.ty != path.ty || existing.sequence != path.sequence {
+                return Err(PathCollision {
+                    id: path.id,
+                    existing: (existing.ty, existing.sequence.clone()),
+                    colliding: (path.ty, path.sequence),
+                });
+            }
+        }
+        Ok(self.paths.entry(path.id.clone()).or_insert(path))
+    }
+
+    /// Inserts a path that has been stored with a previous version of bruss, checking that its id
+    /// still matches the sequence. If it doesn't, the path is recorded in `stale` and it's
+    /// inserted with its current id.
+    pub fn insert_stored(&mut self, mut path: Path) -> Result<&Path, PathCollision> {
+        let expected = HashVersion::CURRENT.hash(path.ty, &path.sequence);
+        if path.id != expected {
+            let version = path.verify_id();
+            let id = std::mem::replace(&mut path.id, expected.clone());
+            self.stale.push(StalePath { id, expected, version });
+        }
+        self.insert(path)
+    }
+
+    /// Converts `trip` and registers the path it follows, with the `RoutingType` of its route.
+    pub fn ingest(&mut self, trip: TTTrip, routes: &HashMap<u16, Route>) -> Result<(Trip, TimeDelta), IngestError> {
+        let (trip, dep, sequence) = Trip::try_from_tt_with_sequence(trip)?;
+        let route = match routes.get(&trip.route) {
+            Some(r) => r,
+            None => return Err(IngestError::UnknownRoute { trip: trip.id, route: trip.route }),
+        };
+        let rty = route.try_routing_type().ok_or(IngestError::UnknownRouteType { route: route.id, ty: route.ty })?;
+        let path = Path::new(sequence, trip.ty, rty);
+        self.register(&trip, path)?;
+        Ok((trip, dep))
+    }
+
+    /// Registers `path` as the path followed by `trip`, that has already been converted.
+    pub fn register(&mut self, trip: &Trip, path: Path) -> Result<(), PathCollision> {
+        debug_assert_eq!(path.id, trip.path);
+        self.insert(path)?;
+        if let Some(old) = self.trips.insert(trip.id.clone(), trip.path.clone()) {
+            // the same trip has been ingested twice
+            *self.usage.get_mut(&old).unwrap() -= 1;
+        }
+        *self.usage.entry(trip.path.clone()).or_default() += 1;
+        Ok(())
+    }
+
+    /// Returns the path followed by the ingested trip `trip`.
+    pub fn path_of(&self, trip: &str) -> Option<&Path> {
+        self.trips.get(trip).and_then(|p| self.paths.get(p))
+    }
+
+    /// Number of ingested trips that follow the path `id`.
+    pub fn trip_count(&self, id: &str) -> usize {
+        self.usage.get(id).copied().unwrap_or(0)
+    }
+
+    /// Returns the stop pairs used by the registered paths that aren't covered by any of
+    /// `segments`, sorted by area and stops.
+    pub fn missing_segments<'a>(&self, segments: impl IntoIterator<Item = &'a Segment>) -> Vec<(AreaType, StopPair)> {
+        let mut known: [HashSet<StopPair>; 2] = Default::default();
+        for s in segments {
+            known[area_index(s.ty)].insert((s.from, s.to));
+        }
+        let mut missing: [HashSet<StopPair>; 2] = Default::default();
+        for p in self.paths.values() {
+            let i = area_index(p.ty);
+            for pair in p.segments() {
+                if !known[i].contains(&pair) {
+                    missing[i].insert(pair);
+                }
+            }
+        }
+        let [urban, extra] = missing;
+        let mut urban = urban.into_iter().collect::<Vec<_>>();
+        let mut extra = extra.into_iter().collect::<Vec<_>>();
+        urban.sort();
+        extra.sort();
+        urban.into_iter().map(|p| (AreaType::U, p))
+            .chain(extra.into_iter().map(|p| (AreaType::E, p)))
+            .collect()
+    }
+
+    pub fn get(&self, id: &str) -> Option<&Path> {
+        self.paths.get(id)
+    }
+
+    pub fn contains(&self, id: &str) -> bool {
+        self.paths.contains_key(id)
+    }
+
+    /// Stored paths whose id didn't match their sequence.
+    pub fn stale(&self) -> &[StalePath] {
+        &self.stale
+    }
+
+    pub fn paths(&self) -> impl Iterator<Item = &Path> {
+        self.paths.values()
+    }
+
+    pub fn into_paths(self) -> impl Iterator<Item = Path> {
+        self.paths.into_values()
+    }
+
+    pub fn len(&self) -> usize {
+        self.paths.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.paths.is_empty()
+    }
+}
+
+fn area_index(ty: AreaType) -> usize {
+    match ty {
+        AreaType::U => 0,
+        AreaType::E => 1,
+    }
+}
+
+#[test]
+fn path_registry_test() {
+    use crate::{sequence_hash, RoutingType};
+
+    let mut r = PathRegistry::new();
+    let p = Path::new(vec![1, 2, 3], AreaType::E, RoutingType::Bus);
+    let id = p.id.clone();
+    assert_eq!(p.verify_id(), Some(HashVersion::V1));
+    r.insert(p).unwrap();
+    r.insert(Path::new(vec![1, 2, 3], AreaType::E, RoutingType::Bus)).unwrap();
+    assert_eq!(r.len(), 1);
+
+    let mut forged = Path::new(vec![3, 2, 1], AreaType::E, RoutingType::Bus);
+    forged.id = id.clone();
+    assert_eq!(forged.verify_id(), None);
+    assert!(r.insert(forged).is_err());
+
+    let mut old = Path::new(vec![4, 5], AreaType::U, RoutingType::Bus);
+    old.id = sequence_hash(AreaType::U, &[4, 5]);
+    r.insert_stored(old).unwrap();
+    assert_eq!(r.stale().len(), 1);
+    assert_eq!(r.stale()[0].version, Some(HashVersion::Legacy));
+    assert!(r.contains(&r.stale()[0].expected));
+}
+
+#[test]
+fn path_registry_test_ingest() {
+    use crate::{test_util::tt_trip, RoutingType};
+
+    let routes = HashMap::from([
+        (5, Route::new(5, 3, 1, AreaType::U, String::new(), String::new(), String::new())),
+        (6, Route::new(6, 9, 1, AreaType::U, String::new(), String::new(), String::new())),
+    ]);
+    let mut r = PathRegistry::new();
+    let (a, _) = r.ingest(tt_trip("a", 5, &[1, 2, 3], "08:00"), &routes).unwrap();
+    let (b, dep) = r.ingest(tt_trip("b", 5, &[1, 2, 3], "09:00"), &routes).unwrap();
+    assert_eq!(dep, TimeDelta::hours(9));
+    r.ingest(tt_trip("c", 5, &[3, 4], "09:00"), &routes).unwrap();
+
+    // trips on the same stops share the path
+    assert_eq!(a.path, b.path);
+    assert_eq!(r.len(), 2);
+    assert_eq!(r.trip_count(&a.path), 2);
+    assert_eq!(r.path_of("b").map(|p| p.sequence.clone()), Some(vec![1, 2, 3]));
+    assert_eq!(r.path_of("b").map(|p| p.rty), Some(RoutingType::Bus));
+    // ingesting a trip again doesn't count it twice
+    r.ingest(tt_trip("a", 5, &[1, 2, 3], "08:00"), &routes).unwrap();
+    assert_eq!(r.trip_count(&a.path), 2);
+
+    assert!(matches!(r.ingest(tt_trip("d", 6, &[1, 2], "08:00"), &routes), Err(IngestError::UnknownRouteType { route: 6, ty: 9 })));
+    assert!(matches!(r.ingest(tt_trip("e", 7, &[1, 2], "08:00"), &routes), Err(IngestError::UnknownRoute { route: 7, .. })));
+
+    let mut forged = Path::new(vec![9, 8], AreaType::U, RoutingType::Bus);
+    forged.id = a.path.clone();
+    let mut d = a;
+    d.id = "d".to_string();
+    assert!(r.register(&d, forged).is_err());
+    assert_eq!(r.trip_count(&d.path), 2);
+
+    let segments = [Segment::new(1, 2, AreaType::U, Vec::new()), Segment::new(2, 3, AreaType::E, Vec::new())];
+    assert_eq!(r.missing_segments(&segments), vec![(AreaType::U, (2, 3)), (AreaType::U, (3, 4))]);
+}
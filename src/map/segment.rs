@@ -8,12 +8,38 @@ pub struct Segment {
     pub to: u16,
     #[serde(rename = "type")]
     pub ty: AreaType,
-    pub geometry: Vec<Coords>
+    pub geometry: Vec<Coords>,
+    /// Length of the segment in meters, as reported by the router.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub distance: Option<f64>,
+    /// Travel time in seconds, as estimated by the router.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub duration: Option<f64>,
+    #[serde(default)]
+    pub source: SegmentSource,
+}
+
+/// Where the geometry of a segment comes from.
+/// Segments stored before its introduction are all routed.
+#[derive(Clone,Copy,Serialize,Deserialize,Debug,Default,PartialEq,Eq,Hash)]
+#[serde(rename_all = "snake_case")]
+pub enum SegmentSource {
+    /// Computed by a routing engine, following the actual network.
+    #[default]
+    Routed,
+    /// Generated without a routing engine: it doesn't follow the network.
+    Fallback,
+    /// Drawn or fixed by hand.
+    Manual,
 }
 
 impl Segment {
     pub fn new(from: u16, to: u16, ty: AreaType, geometry: Vec<Coords>) -> Self {
-        Self { from, to, ty, geometry }
+        Self::new_with_source(from, to, ty, geometry, SegmentSource::default())
+    }
+
+    pub fn new_with_source(from: u16, to: u16, ty: AreaType, geometry: Vec<Coords>, source: SegmentSource) -> Self {
+        Self { from, to, ty, geometry, distance: None, duration: None, source }
     }
 }
 
@@ -51,3 +77,13 @@ fn path_test_from_segments() {
     assert_eq!(p1.sequence, p2.sequence);
 }
 
+
+#[test]
+fn path_test_merge_sequences() {
+    use crate::Path;
+
+    // short turn, branch at the end, and a detour through 7
+    let merged = Path::merge_sequences([&[1, 2, 3, 4, 5][..], &[2, 3], &[1, 2, 3, 6], &[1, 7, 3, 4]]);
+    assert_eq!(merged, vec![1, 2, 7, 3, 4, 5, 6]);
+    assert_eq!(Path::merge_sequences([&[3, 2, 1][..], &[1, 2, 3]]), vec![1, 2, 3, 2, 1]);
+}
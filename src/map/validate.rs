@@ -0,0 +1,203 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tt::AreaType;
+
+use crate::{AreaHelper, Stop};
+use super::{geometry::Projection, RoutingType, Segment};
+
+/// Limits above which a segment is reported.
+#[derive(Clone,Copy,Debug,PartialEq)]
+pub struct Thresholds {
+    /// Maximum distance in meters between an end of the geometry and its stop.
+    pub max_endpoint_error: f64,
+    /// Maximum ratio between the length of the geometry and the straight line distance of the
+    /// two stops.
+    pub max_detour_ratio: f64,
+    /// Geometries shorter than this (in meters) are considered zero length.
+    pub min_length: f64,
+}
+
+impl Thresholds {
+    pub fn for_routing_type(rty: RoutingType) -> Self {
+        match rty {
+            RoutingType::Bus => Self { max_endpoint_error: 50., max_detour_ratio: 3., min_length: 1. },
+            RoutingType::Railway => Self { max_endpoint_error: 100., max_detour_ratio: 2., min_length: 1. },
+            RoutingType::Cableway => Self { max_endpoint_error: 30., max_detour_ratio: 1.2, min_length: 1. },
+        }
+    }
+}
+
+#[derive(Serialize,Debug,Clone,PartialEq)]
+#[serde(tag = "issue", rename_all = "snake_case")]
+pub enum Issue {
+    /// The stop the segment claims to connect doesn't exist.
+    MissingStop { stop: u16 },
+    TooFewPoints { points: usize },
+    ZeroLength,
+    StartSnapping { distance: f64 },
+    EndSnapping { distance: f64 },
+    Detour { ratio: f64 },
+    /// Edges `a` and `b` of the geometry (edge `i` goes from point `i` to `i + 1`) cross each other.
+    SelfIntersection { a: usize, b: usize },
+}
+
+/// Measures and issues of a single segment.
+#[derive(Serialize,Debug,Clone)]
+pub struct SegmentReport {
+    pub from: u16,
+    pub to: u16,
+    #[serde(rename = "type")]
+    pub ty: AreaType,
+    pub length: f64,
+    /// Straight line distance of the two stops, `None` if any of them is missing.
+    pub straight: Option<f64>,
+    pub issues: Vec<Issue>,
+}
+
+impl SegmentReport {
+    pub fn is_ok(&self) -> bool {
+        self.issues.is_empty()
+    }
+}
+
+/// # SegmentValidator
+/// Checks segment geometries against the position of the stops they connect.
+///
+/// Thresholds depend on the routing type: a bus geometry can legitimately wind around a block,
+/// while a cableway should be almost straight.
+#[derive(Clone,Debug,Default)]
+pub struct SegmentValidator {
+    thresholds: HashMap<RoutingType, Thresholds>,
+}
+
+impl SegmentValidator {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Overrides the default thresholds for `rty`.
+    pub fn thresholds(mut self, rty: RoutingType, thresholds: Thresholds) -> Self {
+        self.thresholds.insert(rty, thresholds);
+        self
+    }
+
+    fn get(&self, rty: RoutingType) -> Thresholds {
+        self.thresholds.get(&rty).copied().unwrap_or_else(|| Thresholds::for_routing_type(rty))
+    }
+
+    pub fn validate(&self, segment: &Segment, rty: RoutingType, stops: &AreaHelper<Stop>) -> SegmentReport {
+        let t = self.get(rty);
+        let mut issues = Vec::new();
+        let length = segment.length();
+        let stops = stops.get(segment.ty);
+        let from = stops.get(&segment.from);
+        let to = stops.get(&segment.to);
+        for (id, s) in [(segment.from, from), (segment.to, to)] {
+            if s.is_none() {
+                issues.push(Issue::MissingStop { stop: id });
+            }
+        }
+
+        if segment.geometry.len() < 2 {
+            issues.push(Issue::TooFewPoints { points: segment.geometry.len() });
+        } else if length < t.min_length {
+            issues.push(Issue::ZeroLength);
+        }
+
+        if let (Some(first), Some(from)) = (segment.geometry.first(), from) {
+            let distance = first.haversine(&from.position);
+            if distance > t.max_endpoint_error {
+                issues.push(Issue::StartSnapping { distance });
+            }
+        }
+        if let (Some(last), Some(to)) = (segment.geometry.last(), to) {
+            let distance = last.haversine(&to.position);
+            if distance > t.max_endpoint_error {
+                issues.push(Issue::EndSnapping { distance });
+            }
+        }
+
+        let straight = from.zip(to).map(|(a, b)| a.position.haversine(&b.position));
+        if let Some(straight) = straight {
+            // stops too close to each other are better checked by the endpoint errors
+            if straight >= t.min_length && length / straight > t.max_detour_ratio {
+                issues.push(Issue::Detour { ratio: length / straight });
+            }
+        }
+
+        issues.extend(self_intersections(segment).into_iter().map(|(a, b)| Issue::SelfIntersection { a, b }));
+
+        SegmentReport { from: segment.from, to: segment.to, ty: segment.ty, length, straight, issues }
+    }
+
+    /// Validates many segments, returning only the reports with at least one issue.
+    pub fn validate_all<'a>(&self, segments: impl IntoIterator<Item = (&'a Segment, RoutingType)>, stops: &AreaHelper<Stop>) -> Vec<SegmentReport> {
+        segments.into_iter()
+            .map(|(s, rty)| self.validate(s, rty, stops))
+            .filter(|r| !r.is_ok())
+            .collect()
+    }
+}
+
+/// Pairs of non adjacent edges of the geometry that cross each other.
+fn self_intersections(segment: &Segment) -> Vec<(usize, usize)> {
+    if segment.geometry.len() < 4 {
+        return Vec::new();
+    }
+    let proj = Projection::new(&segment.geometry[0]);
+    let p = segment.geometry.iter().map(|c| proj.project(c)).collect::<Vec<_>>();
+    let mut o = Vec::new();
+    for a in 0..p.len() - 1 {
+        for b in a + 2..p.len() - 1 {
+            if intersects(p[a], p[a + 1], p[b], p[b + 1]) {
+                o.push((a, b));
+            }
+        }
+    }
+    o
+}
+
+fn intersects(p1: (f64, f64), p2: (f64, f64), q1: (f64, f64), q2: (f64, f64)) -> bool {
+    fn orientation(a: (f64, f64), b: (f64, f64), c: (f64, f64)) -> f64 {
+        (b.0 - a.0) * (c.1 - a.1) - (b.1 - a.1) * (c.0 - a.0)
+    }
+    let d1 = orientation(q1, q2, p1);
+    let d2 = orientation(q1, q2, p2);
+    let d3 = orientation(p1, p2, q1);
+    let d4 = orientation(p1, p2, q2);
+    // only proper crossings: touching edges (e.g. a geometry that stops and goes back) are fine
+    d1 * d2 < 0. && d3 * d4 < 0.
+}
+
+#[test]
+fn validate_test_segment() {
+    use crate::{test_util, Coords};
+
+    let stop = |id, position| test_util::stop(AreaType::U, id, "", position);
+    let stops = AreaHelper::from_iter([
+        stop(1, Coords::new(46.0700, 11.1200)),
+        stop(2, Coords::new(46.0710, 11.1200)),
+    ]);
+    let v = SegmentValidator::new();
+
+    let good = Segment::new(1, 2, AreaType::U, vec![Coords::new(46.0700, 11.1200), Coords::new(46.0710, 11.1200)]);
+    assert!(v.validate(&good, RoutingType::Bus, &stops).is_ok());
+
+    // starts 1km away, then loops over itself
+    let bad = Segment::new(1, 2, AreaType::U, vec![
+        Coords::new(46.0790, 11.1200),
+        Coords::new(46.0700, 11.1200),
+        Coords::new(46.0705, 11.1210),
+        Coords::new(46.0705, 11.1190),
+        Coords::new(46.0710, 11.1200),
+    ]);
+    let r = v.validate(&bad, RoutingType::Bus, &stops);
+    assert!(matches!(r.issues[0], Issue::StartSnapping { .. }));
+    assert!(r.issues.iter().any(|i| matches!(i, Issue::Detour { .. })));
+    assert!(r.issues.contains(&Issue::SelfIntersection { a: 0, b: 2 }));
+
+    let missing = Segment::new(1, 3, AreaType::U, vec![Coords::new(46.0700, 11.1200), Coords::new(46.0700, 11.1200)]);
+    let r = v.validate(&missing, RoutingType::Bus, &stops);
+    assert_eq!(r.issues, vec![Issue::MissingStop { stop: 3 }, Issue::ZeroLength]);
+}
@@ -0,0 +1,408 @@
+use std::{collections::{BTreeSet, BinaryHeap, HashMap}, fmt::Write};
+
+use crate::{helpers::MinCost, AreaHelper, Coords, Path, Segment, Stop, StopKey, Trip};
+
+/// A directed connection between two consecutive stops of at least one path.
+#[derive(Debug, Clone)]
+pub struct Edge {
+    pub from: StopKey,
+    pub to: StopKey,
+    /// Length of the `Segment` joining the two stops, if known.
+    pub length: Option<f64>,
+    pub routes: BTreeSet<u16>,
+    pub paths: BTreeSet<String>,
+}
+
+/// # Network
+/// Directed graph of the transit network: nodes are stops, edges join the consecutive stops of
+/// paths and know which routes and paths use them.
+///
+/// Build it by adding stops, paths, trips (to know the routes of each path) and segments (to
+/// know the length of each edge), in any order: trips and segments added before their path are
+/// kept aside and applied when the path is added.
+#[derive(Debug, Default)]
+pub struct Network {
+    nodes: Vec<StopKey>,
+    index: HashMap<StopKey, usize>,
+    positions: HashMap<StopKey, (Coords, String)>,
+    edges: Vec<Edge>,
+    edge_index: HashMap<(usize, usize), usize>,
+    outgoing: Vec<Vec<usize>>,
+    incoming: Vec<Vec<usize>>,
+    path_edges: HashMap<String, Vec<usize>>,
+    /// Routes of trips whose path hasn't been added yet, by path id.
+    pending_routes: HashMap<String, BTreeSet<u16>>,
+    /// Lengths of segments whose edge doesn't exist yet.
+    pending_lengths: HashMap<(StopKey, StopKey), f64>,
+}
+
+impl Network {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    fn node(&mut self, key: StopKey) -> usize {
+        if let Some(i) = self.index.get(&key) {
+            return *i;
+        }
+        self.nodes.push(key);
+        self.outgoing.push(Vec::new());
+        self.incoming.push(Vec::new());
+        self.index.insert(key, self.nodes.len() - 1);
+        self.nodes.len() - 1
+    }
+
+    fn edge(&mut self, from: StopKey, to: StopKey) -> usize {
+        let (a, b) = (self.node(from), self.node(to));
+        if let Some(e) = self.edge_index.get(&(a, b)) {
+            return *e;
+        }
+        let length = self.pending_lengths.remove(&(from, to));
+        self.edges.push(Edge { from, to, length, routes: BTreeSet::new(), paths: BTreeSet::new() });
+        let e = self.edges.len() - 1;
+        self.edge_index.insert((a, b), e);
+        self.outgoing[a].push(e);
+        self.incoming[b].push(e);
+        e
+    }
+
+    /// Adds all the stops as nodes, even the ones that no path serves.
+    pub fn add_stops(&mut self, stops: &AreaHelper<Stop>) {
+        for s in stops.values() {
+            self.node(s.key());
+            self.positions.insert(s.key(), (s.position.clone(), s.name.clone()));
+        }
+    }
+
+    pub fn add_path(&mut self, path: &Path) {
+        if self.path_edges.contains_key(&path.id) || path.sequence.len() < 2 {
+            return;
+        }
+        let routes = self.pending_routes.remove(&path.id).unwrap_or_default();
+        let edges = path.segments().into_iter()
+            .map(|(a, b)| {
+                let e = self.edge(StopKey::new(path.ty, a), StopKey::new(path.ty, b));
+                self.edges[e].paths.insert(path.id.clone());
+                self.edges[e].routes.extend(&routes);
+                e
+            })
+            .collect();
+        self.path_edges.insert(path.id.clone(), edges);
+    }
+
+    /// Marks the edges of the path of `trip` as used by its route.
+    pub fn add_trip(&mut self, trip: &Trip) {
+        match self.path_edges.get(&trip.path) {
+            Some(edges) => for e in edges {
+                self.edges[*e].routes.insert(trip.route);
+            },
+            None => {
+                self.pending_routes.entry(trip.path.clone()).or_default().insert(trip.route);
+            }
+        }
+    }
+
+    /// Sets the length of the edge joining the two stops of `segment`.
+    pub fn add_segment(&mut self, segment: &Segment) {
+        let (from, to) = (StopKey::new(segment.ty, segment.from), StopKey::new(segment.ty, segment.to));
+        let length = segment.distance.unwrap_or_else(|| segment.length());
+        let edge = self.index.get(&from).zip(self.index.get(&to))
+            .and_then(|(a, b)| self.edge_index.get(&(*a, *b)))
+            .copied();
+        match edge {
+            Some(e) => self.edges[e].length = Some(length),
+            None => {
+                self.pending_lengths.insert((from, to), length);
+            }
+        }
+    }
+
+    pub fn nodes(&self) -> &[StopKey] {
+        &self.nodes
+    }
+
+    pub fn edges(&self) -> &[Edge] {
+        &self.edges
+    }
+
+    pub fn outgoing(&self, key: &StopKey) -> impl Iterator<Item = &Edge> {
+        self.index.get(key).into_iter().flat_map(move |i| self.outgoing[*i].iter().map(move |e| &self.edges[*e]))
+    }
+
+    /// Stops that no path serves.
+    pub fn unserved(&self) -> Vec<StopKey> {
+        let mut o = (0..self.nodes.len())
+            .filter(|i| self.outgoing[*i].is_empty() && self.incoming[*i].is_empty())
+            .map(|i| self.nodes[i])
+            .collect::<Vec<_>>();
+        o.sort();
+        o
+    }
+
+    /// Weight of an edge for shortest paths: the segment length, or the straight line distance
+    /// of the two stops if there's no segment.
+    fn weight(&self, e: &Edge) -> Option<f64> {
+        e.length.or_else(|| {
+            let (a, _) = self.positions.get(&e.from)?;
+            let (b, _) = self.positions.get(&e.to)?;
+            Some(a.haversine(b))
+        })
+    }
+
+    /// Shortest path by distance from `from` to `to`, with its length in meters.
+    /// Edges without a segment nor stop positions are ignored.
+    pub fn shortest_path(&self, from: &StopKey, to: &StopKey) -> Option<(Vec<StopKey>, f64)> {
+        let (a, b) = (*self.index.get(from)?, *self.index.get(to)?);
+        let mut dist = vec![f64::INFINITY; self.nodes.len()];
+        let mut prev = vec![usize::MAX; self.nodes.len()];
+        let mut heap = BinaryHeap::new();
+        dist[a] = 0.;
+        heap.push(MinCost { cost: 0., node: a });
+        while let Some(MinCost { cost, node }) = heap.pop() {
+            if node == b {
+                let mut path = vec![b];
+                while *path.last().unwrap() != a {
+                    path.push(prev[*path.last().unwrap()]);
+                }
+                return Some((path.into_iter().rev().map(|i| self.nodes[i]).collect(), cost));
+            }
+            if cost > dist[node] {
+                continue;
+            }
+            for e in &self.outgoing[node] {
+                let Some(w) = self.weight(&self.edges[*e]) else { continue };
+                let next = self.index[&self.edges[*e].to];
+                if cost + w < dist[next] {
+                    dist[next] = cost + w;
+                    prev[next] = node;
+                    heap.push(MinCost { cost: cost + w, node: next });
+                }
+            }
+        }
+        None
+    }
+
+    /// Connected components, ignoring the direction of edges. Unserved stops are components of
+    /// their own. Components are sorted by size, largest first.
+    pub fn components(&self) -> Vec<Vec<StopKey>> {
+        let mut seen = vec![false; self.nodes.len()];
+        let mut o = Vec::new();
+        for start in 0..self.nodes.len() {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![start];
+            let mut component = Vec::new();
+            while let Some(n) = stack.pop() {
+                component.push(self.nodes[n]);
+                let neighbours = self.outgoing[n].iter().map(|e| &self.edges[*e].to)
+                    .chain(self.incoming[n].iter().map(|e| &self.edges[*e].from));
+                for k in neighbours {
+                    let i = self.index[k];
+                    if !seen[i] {
+                        seen[i] = true;
+                        stack.push(i);
+                    }
+                }
+            }
+            component.sort();
+            o.push(component);
+        }
+        o.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        o
+    }
+
+    /// Whether all the served stops are connected, ignoring the direction of edges.
+    pub fn is_connected(&self) -> bool {
+        self.components().into_iter().filter(|c| c.len() > 1).count() <= 1
+    }
+
+    /// Strongly connected components (Kosaraju), sorted by size, largest first.
+    /// A stop that can be reached but not left (or vice versa) is a component of its own.
+    pub fn strongly_connected_components(&self) -> Vec<Vec<StopKey>> {
+        let n = self.nodes.len();
+        // first pass: order of completion of a dfs on the graph
+        let mut order = Vec::with_capacity(n);
+        let mut seen = vec![false; n];
+        for start in 0..n {
+            if seen[start] {
+                continue;
+            }
+            seen[start] = true;
+            let mut stack = vec![(start, 0)];
+            while let Some((node, i)) = stack.pop() {
+                if let Some(e) = self.outgoing[node].get(i) {
+                    stack.push((node, i + 1));
+                    let next = self.index[&self.edges[*e].to];
+                    if !seen[next] {
+                        seen[next] = true;
+                        stack.push((next, 0));
+                    }
+                } else {
+                    order.push(node);
+                }
+            }
+        }
+        // second pass: dfs on the transposed graph, in reverse order of completion
+        let mut component = vec![usize::MAX; n];
+        let mut o: Vec<Vec<StopKey>> = Vec::new();
+        for start in order.into_iter().rev() {
+            if component[start] != usize::MAX {
+                continue;
+            }
+            let c = o.len();
+            o.push(Vec::new());
+            component[start] = c;
+            let mut stack = vec![start];
+            while let Some(node) = stack.pop() {
+                o[c].push(self.nodes[node]);
+                for e in &self.incoming[node] {
+                    let prev = self.index[&self.edges[*e].from];
+                    if component[prev] == usize::MAX {
+                        component[prev] = c;
+                        stack.push(prev);
+                    }
+                }
+            }
+        }
+        for c in o.iter_mut() {
+            c.sort();
+        }
+        o.sort_by(|a, b| b.len().cmp(&a.len()).then_with(|| a.cmp(b)));
+        o
+    }
+
+    fn sorted_nodes(&self) -> Vec<&StopKey> {
+        let mut o = self.nodes.iter().collect::<Vec<_>>();
+        o.sort();
+        o
+    }
+
+    fn sorted_edges(&self) -> Vec<&Edge> {
+        let mut o = self.edges.iter().collect::<Vec<_>>();
+        o.sort_by_key(|e| (e.from, e.to));
+        o
+    }
+
+    /// Graphviz representation of the network.
+    pub fn to_dot(&self) -> String {
+        let mut o = String::from("digraph network {\n");
+        for k in self.sorted_nodes() {
+            let label = match self.positions.get(k) {
+                Some((_, name)) => format!("{} {}", k, name),
+                None => k.to_string(),
+            };
+            writeln!(o, "    \"{}\" [label=\"{}\"];", k, escape_dot(&label)).unwrap();
+        }
+        for e in self.sorted_edges() {
+            let routes = e.routes.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(",");
+            writeln!(o, "    \"{}\" -> \"{}\" [label=\"{}\"];", e.from, e.to, routes).unwrap();
+        }
+        o.push_str("}\n");
+        o
+    }
+
+    /// GraphML representation of the network, with stop names and positions and edge lengths,
+    /// routes and paths as attributes.
+    pub fn to_graphml(&self) -> String {
+        let mut o = String::from(concat!(
+            "<?xml version=\"1.0\" encoding=\"UTF-8\"?>\n",
+            "<graphml xmlns=\"http://graphml.graphdrawing.org/xmlns\">\n",
+            "  <key id=\"name\" for=\"node\" attr.name=\"name\" attr.type=\"string\"/>\n",
+            "  <key id=\"lat\" for=\"node\" attr.name=\"lat\" attr.type=\"double\"/>\n",
+            "  <key id=\"lng\" for=\"node\" attr.name=\"lng\" attr.type=\"double\"/>\n",
+            "  <key id=\"length\" for=\"edge\" attr.name=\"length\" attr.type=\"double\"/>\n",
+            "  <key id=\"routes\" for=\"edge\" attr.name=\"routes\" attr.type=\"string\"/>\n",
+            "  <key id=\"paths\" for=\"edge\" attr.name=\"paths\" attr.type=\"string\"/>\n",
+            "  <graph id=\"network\" edgedefault=\"directed\">\n",
+        ));
+        for k in self.sorted_nodes() {
+            match self.positions.get(k) {
+                Some((c, name)) => {
+                    writeln!(o, "    <node id=\"{}\">", k).unwrap();
+                    writeln!(o, "      <data key=\"name\">{}</data>", escape_xml(name)).unwrap();
+                    writeln!(o, "      <data key=\"lat\">{}</data>", c.lat).unwrap();
+                    writeln!(o, "      <data key=\"lng\">{}</data>", c.lng).unwrap();
+                    o.push_str("    </node>\n");
+                }
+                None => writeln!(o, "    <node id=\"{}\"/>", k).unwrap(),
+            }
+        }
+        for e in self.sorted_edges() {
+            writeln!(o, "    <edge source=\"{}\" target=\"{}\">", e.from, e.to).unwrap();
+            if let Some(l) = e.length {
+                writeln!(o, "      <data key=\"length\">{}</data>", l).unwrap();
+            }
+            let routes = e.routes.iter().map(|r| r.to_string()).collect::<Vec<_>>().join(" ");
+            writeln!(o, "      <data key=\"routes\">{}</data>", routes).unwrap();
+            let paths = e.paths.iter().cloned().collect::<Vec<_>>().join(" ");
+            writeln!(o, "      <data key=\"paths\">{}</data>", escape_xml(&paths)).unwrap();
+            o.push_str("    </edge>\n");
+        }
+        o.push_str("  </graph>\n</graphml>\n");
+        o
+    }
+}
+
+fn escape_dot(s: &str) -> String {
+    s.replace('\\', "\\\\").replace('"', "\\\"")
+}
+
+fn escape_xml(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[test]
+fn network_test() {
+    use tt::AreaType;
+    use crate::{test_util::stop, RoutingType};
+
+    let stops = AreaHelper::from_iter((1..=5).map(|i| stop(AreaType::U, i, &format!("Stop {}", i), Coords::new(46. + i as f64 * 0.001, 11.12))));
+    let mut n = Network::new();
+    n.add_stops(&stops);
+    // 1 -> 2 -> 3 -> 1 is a loop, 3 -> 4 leaves it, 5 is unserved
+    n.add_path(&Path::new(vec![1, 2, 3, 4], AreaType::U, RoutingType::Bus));
+    n.add_path(&Path::new(vec![3, 1], AreaType::U, RoutingType::Bus));
+
+    let k = |id| StopKey::new(AreaType::U, id);
+    assert_eq!(n.unserved(), vec![k(5)]);
+    assert!(n.is_connected());
+    assert_eq!(n.components().len(), 2);
+    let scc = n.strongly_connected_components();
+    assert_eq!(scc[0], vec![k(1), k(2), k(3)]);
+    assert_eq!(scc.len(), 3);
+
+    let (path, length) = n.shortest_path(&k(2), &k(4)).unwrap();
+    assert_eq!(path, vec![k(2), k(3), k(4)]);
+    assert!((length - 222.4).abs() < 1., "{}", length);
+    assert!(n.shortest_path(&k(4), &k(1)).is_none());
+
+    assert!(n.to_dot().contains("    \"U1\" -> \"U2\" [label=\"\"];\n"));
+    assert!(n.to_graphml().contains("<edge source=\"U3\" target=\"U1\">"));
+}
+
+#[test]
+fn network_test_any_order() {
+    use tt::AreaType;
+    use crate::{test_util::trip, RoutingType};
+
+    let path = Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus);
+    let trip = trip("a", 5, &path);
+    let mut segment = Segment::new(2, 3, AreaType::U, Vec::new());
+    segment.distance = Some(150.);
+
+    let mut before = Network::new();
+    before.add_path(&path);
+    before.add_trip(&trip);
+    before.add_segment(&segment);
+    let mut after = Network::new();
+    after.add_segment(&segment);
+    after.add_trip(&trip);
+    after.add_path(&path);
+
+    for n in [before, after] {
+        let edges = n.edges().iter().map(|e| (e.from.id, e.to.id, e.length, e.routes.iter().copied().collect::<Vec<_>>())).collect::<Vec<_>>();
+        assert_eq!(edges, vec![(1, 2, None, vec![5]), (2, 3, Some(150.), vec![5])]);
+    }
+}
@@ -0,0 +1,448 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, TimeDelta, Utc};
+use serde::Serialize;
+
+use crate::{AreaHelper, Path, PathRegistry, Schedule, Stop, StopKey, Transfer};
+
+/// One part of a journey.
+#[derive(Serialize,Debug,Clone,PartialEq)]
+#[serde(tag = "mode", rename_all = "snake_case")]
+pub enum Leg {
+    Ride {
+        /// Id of the trip (and of its schedule).
+        trip: String,
+        route: u16,
+        from: StopKey,
+        to: StopKey,
+        departure: DateTime<Utc>,
+        arrival: DateTime<Utc>,
+    },
+    Walk {
+        from: StopKey,
+        to: StopKey,
+        departure: DateTime<Utc>,
+        arrival: DateTime<Utc>,
+    },
+}
+
+impl Leg {
+    pub fn departure(&self) -> DateTime<Utc> {
+        match self {
+            Self::Ride { departure, .. } | Self::Walk { departure, .. } => *departure,
+        }
+    }
+}
+
+/// An itinerary from the origin to the destination of a `Query`.
+#[derive(Serialize,Debug,Clone,PartialEq)]
+pub struct Journey {
+    pub departure: DateTime<Utc>,
+    pub arrival: DateTime<Utc>,
+    pub legs: Vec<Leg>,
+}
+
+impl Journey {
+    pub fn rides(&self) -> usize {
+        self.legs.iter().filter(|l| matches!(l, Leg::Ride { .. })).count()
+    }
+
+    pub fn transfers(&self) -> usize {
+        self.rides().saturating_sub(1)
+    }
+}
+
+/// # Query
+/// A journey request: from a stop to another, leaving not before `departure`.
+#[derive(Debug,Clone)]
+pub struct Query {
+    from: StopKey,
+    to: StopKey,
+    departure: DateTime<Utc>,
+    wheelchair: bool,
+    max_transfers: usize,
+}
+
+impl Query {
+    pub const DEFAULT_MAX_TRANSFERS: usize = 4;
+
+    pub fn new(from: StopKey, to: StopKey, departure: DateTime<Utc>) -> Self {
+        Self { from, to, departure, wheelchair: false, max_transfers: Self::DEFAULT_MAX_TRANSFERS }
+    }
+
+    /// Only board and alight at stops with `wheelchair_boarding`.
+    pub fn wheelchair(mut self, wheelchair: bool) -> Self {
+        self.wheelchair = wheelchair;
+        self
+    }
+
+    pub fn max_transfers(mut self, max_transfers: usize) -> Self {
+        self.max_transfers = max_transfers;
+        self
+    }
+}
+
+/// Times of a trip at each stop of its pattern, as unix timestamps: `(arrival, departure)`.
+struct TripTimes {
+    id: String,
+    route: u16,
+    times: Vec<(i64, i64)>,
+}
+
+/// Trips following the same path.
+struct Pattern {
+    stops: Vec<usize>,
+    trips: Vec<TripTimes>,
+}
+
+impl Pattern {
+    /// Trip leaving `pos` the earliest, not before `time`.
+    fn earliest_trip(&self, pos: usize, time: i64) -> Option<usize> {
+        self.trips.iter()
+            .enumerate()
+            .filter(|(_, t)| t.times[pos].1 >= time)
+            .min_by_key(|(_, t)| t.times[pos].1)
+            .map(|(i, _)| i)
+    }
+}
+
+#[derive(Clone,Copy)]
+struct Ride {
+    pattern: usize,
+    trip: usize,
+    board: usize,
+    alight: usize,
+    /// Round the ride was taken in: the boarding label is in the previous one.
+    round: usize,
+}
+
+/// Best known way to reach a stop: an optional ride followed by an optional walk.
+#[derive(Clone,Copy)]
+struct Label {
+    arrival: i64,
+    ride: Option<Ride>,
+    /// Stop the walk starts from and its duration.
+    walk: Option<(usize, i64)>,
+}
+
+/// Parameters of a run of RAPTOR.
+struct Search {
+    wheelchair: bool,
+    /// Maximum number of rounds.
+    rides: usize,
+    /// Stop of a one-to-one search: labels that don't improve its arrival are pruned.
+    target: Option<usize>,
+    /// Labels arriving at or after this time are pruned.
+    deadline: i64,
+}
+
+impl Search {
+    /// Arrivals not earlier than this can't improve the result.
+    fn bound(&self, labels: &[Option<Label>]) -> i64 {
+        self.target.map_or(i64::MAX, |t| arrival(&labels[t])).min(self.deadline)
+    }
+}
+
+/// # Planner
+/// Journey planner implementing RAPTOR (Round-bAsed Public Transit Optimized Router) over
+/// schedules: round `k` finds the earliest arrival at every stop with at most `k` rides, so the
+/// journeys returned by `plan` are Pareto-optimal on arrival time and number of transfers.
+///
+/// Trips are grouped by `Path`, the times at each stop come from `ScheduleHints.times`. Trips of
+/// paths passing twice through the same stop can't be told apart at that stop and give wrong
+/// results there.
+#[derive(Default)]
+pub struct Planner {
+    stops: Vec<StopKey>,
+    index: HashMap<StopKey, usize>,
+    accessible: Vec<bool>,
+    patterns: Vec<Pattern>,
+    pattern_index: HashMap<String, usize>,
+    /// For every stop, the patterns passing through it with the position of the stop.
+    stop_patterns: Vec<Vec<(usize, usize)>>,
+    footpaths: Vec<Vec<(usize, i64)>>,
+}
+
+impl Planner {
+    pub fn new(stops: &AreaHelper<Stop>) -> Self {
+        let mut o = Self::default();
+        for s in stops.values() {
+            let i = o.stop(s.key());
+            o.accessible[i] = s.wheelchair_boarding;
+        }
+        o
+    }
+
+    fn stop(&mut self, key: StopKey) -> usize {
+        if let Some(i) = self.index.get(&key) {
+            return *i;
+        }
+        self.stops.push(key);
+        self.accessible.push(false);
+        self.stop_patterns.push(Vec::new());
+        self.footpaths.push(Vec::new());
+        self.index.insert(key, self.stops.len() - 1);
+        self.stops.len() - 1
+    }
+
+    /// Adds a scheduled trip following `path`.
+    /// Returns `false`, ignoring the schedule, if it has no time for some stop of the path.
+    pub fn add_schedule(&mut self, schedule: &Schedule, path: &Path) -> bool {
+        let base = schedule.departure.timestamp();
+        let times = path.sequence.iter()
+            .map(|s| schedule.hints.times.get(s).map(|t| (base + t.arrival.num_seconds(), base + t.departure.num_seconds())))
+            .collect::<Option<Vec<_>>>();
+        let Some(times) = times else { return false };
+        let p = match self.pattern_index.get(&path.id) {
+            Some(p) => *p,
+            None => {
+                let stops = path.sequence.iter().map(|s| self.stop(StopKey::new(path.ty, *s))).collect::<Vec<_>>();
+                let p = self.patterns.len();
+                for (pos, s) in stops.iter().enumerate() {
+                    self.stop_patterns[*s].push((p, pos));
+                }
+                self.patterns.push(Pattern { stops, trips: Vec::new() });
+                self.pattern_index.insert(path.id.clone(), p);
+                p
+            }
+        };
+        self.patterns[p].trips.push(TripTimes { id: schedule.id.clone(), route: schedule.hints.route, times });
+        true
+    }
+
+    /// Adds many schedules, taking the path of each one from `paths`. Returns how many schedules
+    /// were ignored, because their trip isn't registered or because of `add_schedule`.
+    pub fn add_schedules<'a>(&mut self, schedules: impl IntoIterator<Item = &'a Schedule>, paths: &PathRegistry) -> usize {
+        schedules.into_iter()
+            .filter(|s| !paths.path_of(&s.id).is_some_and(|p| self.add_schedule(s, p)))
+            .count()
+    }
+
+    /// Adds a one way walking connection from `from` to `to`.
+    pub fn add_footpath(&mut self, from: StopKey, to: StopKey, duration: TimeDelta) {
+        let (a, b) = (self.stop(from), self.stop(to));
+        self.footpaths[a].push((b, duration.num_seconds()));
+    }
+
+    pub fn add_transfers<'a>(&mut self, transfers: impl IntoIterator<Item = &'a Transfer>) {
+        for t in transfers {
+            self.add_footpath(t.from, t.to, t.duration);
+        }
+    }
+
+    fn usable(&self, search: &Search, stop: usize) -> bool {
+        !search.wheelchair || self.accessible[stop]
+    }
+
+    /// Pareto-optimal journeys for `query`, sorted by number of transfers: each journey arrives
+    /// strictly earlier than the previous one.
+    pub fn plan(&self, query: &Query) -> Vec<Journey> {
+        let (Some(origin), Some(target)) = (self.index.get(&query.from).copied(), self.index.get(&query.to).copied()) else {
+            return Vec::new();
+        };
+        let search = Search { wheelchair: query.wheelchair, rides: query.max_transfers + 1, target: Some(target), deadline: i64::MAX };
+        let rounds = self.raptor(&[(origin, query.departure.timestamp())], &search);
+        let mut best = i64::MAX;
+        let mut journeys = Vec::new();
+        for (k, labels) in rounds.iter().enumerate() {
+            if arrival(&labels[target]) < best {
+                best = arrival(&labels[target]);
+                journeys.push(self.journey(&rounds, k, target, query.departure));
+            }
+        }
+        journeys
+    }
+
+    /// Earliest arrival at every stop that can be reached before `deadline`, starting from the
+    /// `sources` at the given times, with at most `rides` rides.
+    pub fn earliest_arrivals(&self, sources: &[(StopKey, DateTime<Utc>)], deadline: DateTime<Utc>, rides: usize, wheelchair: bool) -> Vec<(StopKey, DateTime<Utc>)> {
+        let sources = sources.iter()
+            .filter_map(|(k, t)| self.index.get(k).map(|i| (*i, t.timestamp())))
+            .collect::<Vec<_>>();
+        let search = Search { wheelchair, rides, target: None, deadline: deadline.timestamp() + 1 };
+        let rounds = self.raptor(&sources, &search);
+        let Some(last) = rounds.last() else { return Vec::new() };
+        let mut o = last.iter()
+            .enumerate()
+            .filter_map(|(i, l)| l.map(|l| (self.stops[i], DateTime::from_timestamp(l.arrival, 0).unwrap())))
+            .collect::<Vec<_>>();
+        o.sort();
+        o
+    }
+
+    /// Runs the RAPTOR rounds: the `k`-th element of the output has the best labels with at most
+    /// `k` rides.
+    fn raptor(&self, sources: &[(usize, i64)], search: &Search) -> Vec<Vec<Option<Label>>> {
+        let mut first = vec![None; self.stops.len()];
+        let mut marked = Vec::new();
+        for (s, t) in sources {
+            if *t < arrival(&first[*s]).min(search.deadline) {
+                first[*s] = Some(Label { arrival: *t, ride: None, walk: None });
+                marked.push(*s);
+            }
+        }
+        self.walk(&mut first, &mut marked, search);
+        let mut rounds = vec![first];
+
+        for k in 1..=search.rides {
+            if marked.is_empty() {
+                break;
+            }
+            let prev = &rounds[k - 1];
+            let mut cur = prev.clone();
+            // scan each pattern only once, from the first marked stop
+            let mut queue: HashMap<usize, usize> = HashMap::new();
+            for s in &marked {
+                for (p, pos) in &self.stop_patterns[*s] {
+                    let e = queue.entry(*p).or_insert(*pos);
+                    *e = (*e).min(*pos);
+                }
+            }
+            let mut improved = Vec::new();
+            for (p, start) in queue {
+                let pattern = &self.patterns[p];
+                let mut riding: Option<(usize, usize)> = None;
+                for pos in start..pattern.stops.len() {
+                    let s = pattern.stops[pos];
+                    if let Some((trip, board)) = riding {
+                        let time = pattern.trips[trip].times[pos].0;
+                        if time < arrival(&cur[s]).min(search.bound(&cur)) && self.usable(search, s) {
+                            cur[s] = Some(Label { arrival: time, ride: Some(Ride { pattern: p, trip, board, alight: pos, round: k }), walk: None });
+                            improved.push(s);
+                        }
+                    }
+                    let Some(l) = prev[s] else { continue };
+                    if pos + 1 == pattern.stops.len() || !self.usable(search, s) {
+                        continue;
+                    }
+                    if let Some(t) = pattern.earliest_trip(pos, l.arrival) {
+                        let better = match riding {
+                            Some((r, _)) => pattern.trips[t].times[pos].1 < pattern.trips[r].times[pos].1,
+                            None => true,
+                        };
+                        if better {
+                            riding = Some((t, pos));
+                        }
+                    }
+                }
+            }
+            improved.sort_unstable();
+            improved.dedup();
+            marked = improved;
+            self.walk(&mut cur, &mut marked, search);
+            rounds.push(cur);
+        }
+        rounds
+    }
+
+    /// Relaxes the footpaths starting from the `marked` stops, adding the stops improved by
+    /// walking to `marked`. Walks are never chained.
+    fn walk(&self, labels: &mut [Option<Label>], marked: &mut Vec<usize>, search: &Search) {
+        let from = marked.iter().filter_map(|s| labels[*s].map(|l| (*s, l))).collect::<Vec<_>>();
+        for (s, l) in from {
+            for (q, d) in &self.footpaths[s] {
+                let time = l.arrival + d;
+                if time < arrival(&labels[*q]).min(search.bound(labels)) {
+                    labels[*q] = Some(Label { arrival: time, ride: l.ride, walk: Some((s, *d)) });
+                    if !marked.contains(q) {
+                        marked.push(*q);
+                    }
+                }
+            }
+        }
+    }
+
+    fn journey(&self, rounds: &[Vec<Option<Label>>], k: usize, target: usize, departure: DateTime<Utc>) -> Journey {
+        let time = |t: i64| DateTime::from_timestamp(t, 0).unwrap();
+        let mut legs = Vec::new();
+        let mut label = rounds[k][target].unwrap();
+        let mut stop = target;
+        loop {
+            if let Some((from, d)) = label.walk {
+                legs.push(Leg::Walk { from: self.stops[from], to: self.stops[stop], departure: time(label.arrival - d), arrival: time(label.arrival) });
+                stop = from;
+            }
+            let Some(r) = label.ride else { break };
+            let pattern = &self.patterns[r.pattern];
+            let trip = &pattern.trips[r.trip];
+            let board = pattern.stops[r.board];
+            legs.push(Leg::Ride {
+                trip: trip.id.clone(),
+                route: trip.route,
+                from: self.stops[board],
+                to: self.stops[pattern.stops[r.alight]],
+                departure: time(trip.times[r.board].1),
+                arrival: time(trip.times[r.alight].0),
+            });
+            label = rounds[r.round - 1][board].unwrap();
+            stop = board;
+        }
+        legs.reverse();
+        Journey {
+            departure: legs.first().map_or(departure, |l| l.departure()),
+            arrival: time(rounds[k][target].unwrap().arrival),
+            legs,
+        }
+    }
+}
+
+/// Arrival time of a label, `i64::MAX` if the stop isn't reached.
+fn arrival(label: &Option<Label>) -> i64 {
+    label.map_or(i64::MAX, |l| l.arrival)
+}
+
+#[test]
+fn planner_test_plan() {
+    use tt::AreaType;
+    use crate::{stop_time::{StopTime, StopTimes}, test_util, Coords, Direction, RoutingType, ScheduleHints};
+
+    let k = |id| StopKey::new(AreaType::U, id);
+    let stop = |id, wheelchair| Stop { wheelchair_boarding: wheelchair, ..test_util::stop(AreaType::U, id, "", Coords::new(46.07, 11.12)) };
+    let stops = AreaHelper::from_iter([stop(1, true), stop(2, true), stop(3, true), stop(4, true), stop(5, false)]);
+    let day = DateTime::parse_from_rfc3339("2024-05-06T00:00:00Z").unwrap().with_timezone(&Utc);
+    let schedule = |id: &str, departure: i64, times: &[(u16, i64)]| Schedule {
+        id: id.to_string(),
+        departure: day + TimeDelta::minutes(departure),
+        arrival: day + TimeDelta::minutes(departure + times.last().unwrap().1),
+        hints: ScheduleHints {
+            route: 1,
+            ty: AreaType::U,
+            direction: Direction::Forward,
+            times: StopTimes(times.iter().map(|(s, t)| (*s, StopTime { arrival: TimeDelta::minutes(*t), departure: TimeDelta::minutes(*t) })).collect()),
+        },
+    };
+
+    let mut p = Planner::new(&stops);
+    // slow direct line 1 -> 4, or a faster one 1 -> 3, a walk to 5, then 5 -> 4
+    let direct = Path::new(vec![1, 4], AreaType::U, RoutingType::Bus);
+    let first = Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus);
+    let second = Path::new(vec![5, 4], AreaType::U, RoutingType::Bus);
+    assert!(p.add_schedule(&schedule("direct", 8 * 60 + 5, &[(1, 0), (4, 35)]), &direct));
+    assert!(p.add_schedule(&schedule("first", 8 * 60, &[(1, 0), (2, 5), (3, 10)]), &first));
+    assert!(p.add_schedule(&schedule("second", 8 * 60 + 13, &[(5, 0), (4, 7)]), &second));
+    assert!(!p.add_schedule(&schedule("broken", 9 * 60, &[(5, 0)]), &second));
+    p.add_footpath(k(3), k(5), TimeDelta::minutes(2));
+
+    let q = Query::new(k(1), k(4), day + TimeDelta::hours(8));
+    let journeys = p.plan(&q);
+    assert_eq!(journeys.len(), 2);
+    assert_eq!(journeys[0].transfers(), 0);
+    assert_eq!(journeys[0].arrival, day + TimeDelta::minutes(8 * 60 + 40));
+    assert_eq!(journeys[1].transfers(), 1);
+    assert_eq!(journeys[1].arrival, day + TimeDelta::minutes(8 * 60 + 20));
+    assert_eq!(journeys[1].legs.len(), 3);
+    assert_eq!(journeys[1].legs[1], Leg::Walk {
+        from: k(3),
+        to: k(5),
+        departure: day + TimeDelta::minutes(8 * 60 + 10),
+        arrival: day + TimeDelta::minutes(8 * 60 + 12),
+    });
+
+    // stop 5 has no wheelchair boarding
+    let journeys = p.plan(&q.clone().wheelchair(true));
+    assert_eq!(journeys.len(), 1);
+    assert_eq!(journeys[0].transfers(), 0);
+
+    // too late for the direct line
+    assert!(p.plan(&Query::new(k(1), k(4), day + TimeDelta::hours(9))).is_empty());
+}
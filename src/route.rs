@@ -24,12 +24,20 @@ impl Route {
     }
 
     pub fn routing_type(&self) -> RoutingType {
-        match self.ty {
-            2 => RoutingType::Railway,
-            3 => RoutingType::Bus,
-            5 => RoutingType::Cableway,
+        match self.try_routing_type() {
+            Some(r) => r,
             // undefined behavior
-            _ => panic!("route type {} is not recognized (valid values are 2 => railway, 3 => bus, 5 => cableway)", self.ty)
+            None => panic!("route type {} is not recognized (valid values are 2 => railway, 3 => bus, 5 => cableway)", self.ty)
+        }
+    }
+
+    /// Like `routing_type`, but returns `None` for unrecognized route types instead of panicking.
+    pub fn try_routing_type(&self) -> Option<RoutingType> {
+        match self.ty {
+            2 => Some(RoutingType::Railway),
+            3 => Some(RoutingType::Bus),
+            5 => Some(RoutingType::Cableway),
+            _ => None
         }
     }
 }
@@ -3,15 +3,21 @@ use std::hash::Hash;
 use chrono::{DateTime, Utc};
 use serde::{Serialize,Deserialize};
 use tt::AreaType;
+use uuid::Uuid;
 
-use crate::{stop_time::StopTimes, BrussType, Direction, Trip};
+use crate::{stop_time::StopTimes, BrussType, Direction, Trip, Type};
 
+/// # Schedule
+/// A trip scheduled at a specific date and time.
+///
+/// `departure` and `arrival` are always serialized as bson datetimes (see `bson_datetime`), so
+/// that the format doesn't depend on the features enabled.
 #[derive(Serialize,Deserialize,Debug)]
 pub struct Schedule {
     pub id: String,
-    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[serde(with = "bson_datetime")]
     pub departure: DateTime<Utc>,
-    #[serde(with = "mongodb::bson::serde_helpers::chrono_datetime_as_bson_datetime")]
+    #[serde(with = "bson_datetime")]
     pub arrival: DateTime<Utc>,
     pub hints: ScheduleHints,
 }
@@ -21,10 +27,18 @@ impl BrussType for Schedule {
 }
 
 impl Schedule {
-    pub fn from_trip(trip: &Trip, departure: DateTime<Utc>) -> Self {
+    /// The trip leaving its first stop at `departure`. Returns `None` if the trip has no stop
+    /// times.
+    pub fn from_trip(trip: &Trip, departure: DateTime<Utc>) -> Option<Self> {
         let hints = ScheduleHints::from(trip);
-        let arrival = departure + hints.times.iter().max_by_key(|(_, v)| v.arrival.max(v.departure)).unwrap().1.departure;
-        Self { id: trip.id.clone(), departure, hints, arrival }
+        let arrival = departure + hints.times.iter().max_by_key(|(_, v)| v.arrival.max(v.departure))?.1.departure;
+        Some(Self { id: trip.id.clone(), departure, hints, arrival })
+    }
+
+    /// Unique identifier of this schedule, UUIDv5 of the trip id and the departure time in the
+    /// namespace of `Type::Schedule`.
+    pub fn uuid(&self) -> Uuid {
+        Uuid::new_v5(&Type::Schedule.namespace(), format!("{}@{}", self.id, self.departure.timestamp()).as_bytes())
     }
 }
 
@@ -45,6 +59,53 @@ impl Hash for Schedule {
     }
 }
 
+/// Serializes a `DateTime<Utc>` the way `bson::DateTime` does, without depending on bson: a
+/// `$date` struct that the bson serializers turn into a datetime, and that other formats render as
+/// canonical extended JSON (`{ "$date": { "$numberLong": "<milliseconds>" } }`).
+mod bson_datetime {
+    use chrono::{DateTime, TimeZone, Utc};
+    use serde::{de::Error, Deserialize, Deserializer, Serialize, Serializer};
+
+    #[derive(Serialize, Deserialize)]
+    #[serde(rename = "$date")]
+    struct Date<T> {
+        #[serde(rename = "$date")]
+        date: T,
+    }
+
+    #[derive(Serialize)]
+    #[serde(rename = "Int64")]
+    struct NumberLong {
+        #[serde(rename = "$numberLong")]
+        value: String,
+    }
+
+    /// The representations of a datetime given by the bson deserializers and by extended JSON.
+    #[derive(Deserialize)]
+    #[serde(untagged)]
+    enum Body {
+        Canonical {
+            #[serde(rename = "$numberLong")]
+            value: String,
+        },
+        Millis(i64),
+        Relaxed(DateTime<Utc>),
+    }
+
+    pub fn serialize<S: Serializer>(value: &DateTime<Utc>, serializer: S) -> Result<S::Ok, S::Error> {
+        Date { date: NumberLong { value: value.timestamp_millis().to_string() } }.serialize(serializer)
+    }
+
+    pub fn deserialize<'de, D: Deserializer<'de>>(deserializer: D) -> Result<DateTime<Utc>, D::Error> {
+        let millis = match Date::<Body>::deserialize(deserializer)?.date {
+            Body::Canonical { value } => value.parse().map_err(D::Error::custom)?,
+            Body::Millis(millis) => millis,
+            Body::Relaxed(date) => return Ok(date),
+        };
+        Utc.timestamp_millis_opt(millis).single().ok_or_else(|| D::Error::custom("datetime out of range"))
+    }
+}
+
 #[derive(Serialize,Deserialize,Debug,PartialEq)]
 pub struct ScheduleHints {
     pub route: u16,
@@ -64,3 +125,25 @@ impl From<&Trip> for ScheduleHints {
         }
     }
 }
+
+#[test]
+fn schedule_test_serialize() {
+    use chrono::TimeZone;
+    use serde_json::json;
+
+    let departure = Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap();
+    let schedule = Schedule {
+        id: "a".to_string(),
+        departure,
+        arrival: departure,
+        hints: ScheduleHints { route: 5, ty: AreaType::U, times: StopTimes(std::collections::HashMap::new()), direction: Direction::Forward },
+    };
+    let value = serde_json::to_value(&schedule).unwrap();
+    assert_eq!(value["departure"], json!({ "$date": { "$numberLong": "1714550400000" } }));
+    assert_eq!(serde_json::from_value::<Schedule>(value).unwrap().departure, departure);
+    let path = crate::Path::new(vec![1, 2], AreaType::U, crate::RoutingType::Bus);
+    let trip = Trip { times: StopTimes(std::collections::HashMap::new()), ..crate::test_util::trip("b", 5, &path) };
+    assert!(Schedule::from_trip(&trip, departure).is_none());
+    #[cfg(feature = "db")]
+    assert_eq!(mongodb::bson::to_document(&schedule).unwrap().get_datetime("arrival").unwrap().timestamp_millis(), 1714550400000);
+}
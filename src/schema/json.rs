@@ -0,0 +1,64 @@
+use serde_json::{json, Map, Value};
+
+use super::Schema;
+
+impl Schema {
+    /// Renders the schema as a (draft 07) JSON Schema.
+    pub fn to_json_schema(&self) -> Value {
+        match self {
+            Schema::Integer { min, max } => {
+                let mut o = Map::new();
+                o.insert("type".to_string(), "integer".into());
+                if let Some(min) = min {
+                    o.insert("minimum".to_string(), (*min).into());
+                }
+                if let Some(max) = max {
+                    o.insert("maximum".to_string(), (*max).into());
+                }
+                Value::Object(o)
+            }
+            Schema::Number => json!({ "type": "number" }),
+            Schema::String => json!({ "type": "string" }),
+            Schema::Enum(values) => json!({ "type": "string", "enum": values }),
+            Schema::Boolean => json!({ "type": "boolean" }),
+            Schema::DateTime => json!({ "type": "string", "format": "date-time" }),
+            // extended json representation of a bson datetime
+            Schema::BsonDateTime => json!({
+                "type": "object",
+                "required": ["$date"],
+                "properties": { "$date": {
+                    "type": "object",
+                    "required": ["$numberLong"],
+                    "properties": { "$numberLong": { "type": "string", "pattern": "^-?[0-9]+$" } },
+                } },
+            }),
+            Schema::Nullable(inner) => json!({ "anyOf": [inner.to_json_schema(), { "type": "null" }] }),
+            Schema::Array(items) => json!({ "type": "array", "items": items.to_json_schema() }),
+            Schema::Tuple(items) => json!({
+                "type": "array",
+                "items": items.iter().map(Schema::to_json_schema).collect::<Vec<_>>(),
+                "minItems": items.len(),
+                "maxItems": items.len(),
+                "additionalItems": false,
+            }),
+            Schema::Map { key, value } => {
+                let mut patterns = Map::new();
+                patterns.insert(key.to_string(), value.to_json_schema());
+                json!({ "type": "object", "patternProperties": patterns, "additionalProperties": false })
+            }
+            Schema::Object(fields) => {
+                let mut o = Map::new();
+                o.insert("type".to_string(), "object".into());
+                let required = fields.iter().filter(|f| f.required).map(|f| f.name).collect::<Vec<_>>();
+                if !required.is_empty() {
+                    o.insert("required".to_string(), required.into());
+                }
+                o.insert("properties".to_string(), Value::Object(fields.iter()
+                    .map(|f| (f.name.to_string(), f.schema.to_json_schema()))
+                    .collect()));
+                Value::Object(o)
+            }
+            Schema::Named(_, inner) => inner.to_json_schema(),
+        }
+    }
+}
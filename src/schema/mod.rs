@@ -0,0 +1,417 @@
+mod json;
+#[cfg(feature = "db")]
+mod mongo;
+// always built in tests, so that stale bindings are caught whenever `schema` is enabled
+#[cfg(any(feature = "typescript", test))]
+pub mod typescript;
+
+#[cfg(feature = "db")]
+pub use mongo::install_validator;
+
+use crate::{Area, Coords, Direction, Path, Route, RoutingType, Schedule, ScheduleHints, Segment, SegmentSource, ServedRoute, Station, Stop, StopKey, StopRoutes, StopTime, StopTimes, Transfer, Trip};
+
+/// # Schema
+/// Description of the serde output of a bruss type.
+///
+/// It is independent of the target format: the same description is rendered as a JSON Schema
+/// (`to_json_schema`) or as a MongoDB `$jsonSchema` validator, that uses `bsonType` instead of the
+/// plain JSON types.
+#[derive(Debug, Clone, PartialEq)]
+pub enum Schema {
+    Integer { min: Option<i64>, max: Option<i64> },
+    Number,
+    String,
+    /// A string that can only take one of the given values.
+    Enum(Vec<&'static str>),
+    Boolean,
+    /// A chrono `DateTime`, serialized as a RFC 3339 string.
+    DateTime,
+    /// A chrono `DateTime`, serialized as a bson datetime.
+    BsonDateTime,
+    Nullable(Box<Schema>),
+    Array(Box<Schema>),
+    /// A fixed size array, with a schema for each position.
+    Tuple(Vec<Schema>),
+    /// An object with arbitrary keys matching `key` (a regex) and values matching `value`.
+    Map { key: &'static str, value: Box<Schema> },
+    Object(Vec<Field>),
+    /// A schema that has a name of its own: it's rendered inline in JSON schemas, but it's
+    /// referenced by name in type definitions.
+    Named(&'static str, Box<Schema>),
+}
+
+#[derive(Debug, Clone, PartialEq)]
+pub struct Field {
+    pub name: &'static str,
+    pub schema: Schema,
+    /// Fields with `skip_serializing_if` may be missing from the output.
+    pub required: bool,
+}
+
+impl Field {
+    pub fn required(name: &'static str, schema: Schema) -> Self {
+        Self { name, schema, required: true }
+    }
+
+    pub fn optional(name: &'static str, schema: Schema) -> Self {
+        Self { name, schema, required: false }
+    }
+}
+
+impl Schema {
+    pub fn u16() -> Self {
+        Self::Integer { min: Some(u16::MIN as i64), max: Some(u16::MAX as i64) }
+    }
+
+    pub fn i32() -> Self {
+        Self::Integer { min: Some(i32::MIN as i64), max: Some(i32::MAX as i64) }
+    }
+
+    pub fn i64() -> Self {
+        Self::Integer { min: None, max: None }
+    }
+
+    pub fn nullable(self) -> Self {
+        Self::Nullable(Box::new(self))
+    }
+
+    pub fn array_of(self) -> Self {
+        Self::Array(Box::new(self))
+    }
+
+    pub fn named<T: HasSchema>() -> Self {
+        Self::Named(T::NAME, Box::new(T::schema()))
+    }
+
+    /// Returns the json schema of `T`, as a standalone document.
+    pub fn document<T: HasSchema>() -> serde_json::Value {
+        let mut o = T::schema().to_json_schema();
+        if let serde_json::Value::Object(m) = &mut o {
+            m.insert("$schema".to_string(), "http://json-schema.org/draft-07/schema#".into());
+            m.insert("title".to_string(), T::NAME.into());
+        }
+        o
+    }
+}
+
+/// Named schemas of all the bruss types, in dependency order.
+pub fn all() -> Vec<Schema> {
+    vec![
+        area_type(),
+        time_delta(),
+        Schema::named::<Coords>(),
+        Schema::named::<Direction>(),
+        Schema::named::<RoutingType>(),
+        Schema::named::<SegmentSource>(),
+        Schema::named::<StopTime>(),
+        Schema::named::<StopTimes>(),
+        Schema::named::<Area>(),
+        Schema::named::<Stop>(),
+        Schema::named::<StopKey>(),
+        Schema::named::<Route>(),
+        Schema::named::<Trip>(),
+        Schema::named::<Path>(),
+        Schema::named::<Segment>(),
+        Schema::named::<ScheduleHints>(),
+        Schema::named::<Schedule>(),
+        Schema::named::<Transfer>(),
+        Schema::named::<Station>(),
+        Schema::named::<ServedRoute>(),
+        Schema::named::<StopRoutes>(),
+    ]
+}
+
+/// Type whose serialized form can be described by a `Schema`.
+pub trait HasSchema {
+    const NAME: &'static str;
+
+    fn schema() -> Schema;
+}
+
+/// `tt::AreaType` is serialized as its variant name.
+fn area_type() -> Schema {
+    Schema::Named("AreaType", Box::new(Schema::Enum(vec!["E", "U"])))
+}
+
+/// `chrono::TimeDelta` is serialized as a `[seconds, nanoseconds]` tuple.
+fn time_delta() -> Schema {
+    Schema::Named("TimeDelta", Box::new(Schema::Tuple(vec![
+        Schema::i64(),
+        Schema::Integer { min: Some(0), max: Some(999_999_999) },
+    ])))
+}
+
+impl HasSchema for Coords {
+    const NAME: &'static str = "Coords";
+
+    fn schema() -> Schema {
+        Schema::Tuple(vec![Schema::Number, Schema::Number])
+    }
+}
+
+impl HasSchema for Direction {
+    const NAME: &'static str = "Direction";
+
+    fn schema() -> Schema {
+        Schema::Enum(vec!["f", "b"])
+    }
+}
+
+impl HasSchema for RoutingType {
+    const NAME: &'static str = "RoutingType";
+
+    fn schema() -> Schema {
+        Schema::Enum(vec!["bus", "railway", "cableway"])
+    }
+}
+
+impl HasSchema for SegmentSource {
+    const NAME: &'static str = "SegmentSource";
+
+    fn schema() -> Schema {
+        Schema::Enum(vec!["routed", "fallback", "manual"])
+    }
+}
+
+impl HasSchema for StopTime {
+    const NAME: &'static str = "StopTime";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("arrival", time_delta()),
+            Field::required("departure", time_delta()),
+        ])
+    }
+}
+
+impl HasSchema for StopTimes {
+    const NAME: &'static str = "StopTimes";
+
+    fn schema() -> Schema {
+        Schema::Map { key: "^[0-9]+$", value: Box::new(Schema::named::<StopTime>()) }
+    }
+}
+
+impl HasSchema for Area {
+    const NAME: &'static str = "Area";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::u16()),
+            Field::required("label", Schema::String),
+            Field::required("type", area_type()),
+        ])
+    }
+}
+
+impl HasSchema for Stop {
+    const NAME: &'static str = "Stop";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::u16()),
+            Field::required("code", Schema::String),
+            Field::required("description", Schema::String),
+            Field::required("position", Schema::named::<Coords>()),
+            Field::required("altitude", Schema::i32()),
+            Field::required("name", Schema::String),
+            Field::required("street", Schema::String.nullable()),
+            Field::required("town", Schema::String.nullable()),
+            Field::required("type", area_type()),
+            Field::required("wheelchair_boarding", Schema::Boolean),
+            Field::optional("station", Schema::String),
+        ])
+    }
+}
+
+impl HasSchema for StopKey {
+    const NAME: &'static str = "StopKey";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("type", area_type()),
+            Field::required("id", Schema::u16()),
+        ])
+    }
+}
+
+impl HasSchema for Route {
+    const NAME: &'static str = "Route";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::u16()),
+            Field::required("type", Schema::u16()),
+            Field::required("area", Schema::u16()),
+            Field::required("area_ty", area_type()),
+            Field::required("color", Schema::String),
+            Field::required("name", Schema::String),
+            Field::required("code", Schema::String),
+        ])
+    }
+}
+
+impl HasSchema for Trip {
+    const NAME: &'static str = "Trip";
+
+    fn schema() -> Schema {
+        // `delay` is never serialized
+        Schema::Object(vec![
+            Field::required("id", Schema::String),
+            Field::required("direction", Schema::named::<Direction>()),
+            Field::optional("next_stop", Schema::u16()),
+            Field::optional("last_stop", Schema::u16()),
+            Field::required("bus_id", Schema::u16().nullable()),
+            Field::required("route", Schema::u16()),
+            Field::required("headsign", Schema::String),
+            Field::required("path", Schema::String),
+            Field::required("times", Schema::named::<StopTimes>()),
+            Field::required("type", area_type()),
+            Field::required("last_event", Schema::DateTime.nullable()),
+        ])
+    }
+}
+
+impl HasSchema for Path {
+    const NAME: &'static str = "Path";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::String),
+            Field::required("type", area_type()),
+            Field::required("sequence", Schema::u16().array_of()),
+            // paths stored before the routing type was introduced don't have it
+            Field::optional("rty", Schema::named::<RoutingType>()),
+        ])
+    }
+}
+
+impl HasSchema for Segment {
+    const NAME: &'static str = "Segment";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("from", Schema::u16()),
+            Field::required("to", Schema::u16()),
+            Field::required("type", area_type()),
+            Field::required("geometry", Schema::named::<Coords>().array_of()),
+            Field::optional("distance", Schema::Number),
+            Field::optional("duration", Schema::Number),
+            // segments stored before the source was tracked don't have it
+            Field::optional("source", Schema::named::<SegmentSource>()),
+        ])
+    }
+}
+
+impl HasSchema for ScheduleHints {
+    const NAME: &'static str = "ScheduleHints";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("route", Schema::u16()),
+            Field::required("type", area_type()),
+            Field::required("times", Schema::named::<StopTimes>()),
+            Field::required("direction", Schema::named::<Direction>()),
+        ])
+    }
+}
+
+impl HasSchema for Schedule {
+    const NAME: &'static str = "Schedule";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::String),
+            Field::required("departure", Schema::BsonDateTime),
+            Field::required("arrival", Schema::BsonDateTime),
+            Field::required("hints", Schema::named::<ScheduleHints>()),
+        ])
+    }
+}
+
+impl HasSchema for Transfer {
+    const NAME: &'static str = "Transfer";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("from", Schema::named::<StopKey>()),
+            Field::required("to", Schema::named::<StopKey>()),
+            Field::required("distance", Schema::Number),
+            Field::required("duration", time_delta()),
+        ])
+    }
+}
+
+impl HasSchema for Station {
+    const NAME: &'static str = "Station";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::String),
+            Field::required("name", Schema::String),
+            Field::required("position", Schema::named::<Coords>()),
+            Field::required("stops", Schema::named::<StopKey>().array_of()),
+        ])
+    }
+}
+
+impl HasSchema for ServedRoute {
+    const NAME: &'static str = "ServedRoute";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("route", Schema::u16()),
+            Field::required("direction", Schema::named::<Direction>()),
+            Field::required("headsigns", Schema::String.array_of()),
+            Field::required("trips", Schema::Integer { min: Some(0), max: Some(u32::MAX as i64) }),
+            Field::required("terminus", Schema::Boolean),
+        ])
+    }
+}
+
+impl HasSchema for StopRoutes {
+    const NAME: &'static str = "StopRoutes";
+
+    fn schema() -> Schema {
+        Schema::Object(vec![
+            Field::required("id", Schema::u16()),
+            Field::required("type", area_type()),
+            Field::required("routes", Schema::named::<ServedRoute>().array_of()),
+        ])
+    }
+}
+
+#[test]
+fn schema_test_coords() {
+    use serde_json::json;
+
+    let s = Schema::document::<Coords>();
+    assert_eq!(s["type"], json!("array"));
+    assert_eq!(s["minItems"], json!(2));
+    assert_eq!(s["maxItems"], json!(2));
+    assert_eq!(s["items"], json!([{ "type": "number" }, { "type": "number" }]));
+
+    let s = StopTimes::schema().to_json_schema();
+    assert_eq!(s["patternProperties"]["^[0-9]+$"]["required"], json!(["arrival", "departure"]));
+    assert_eq!(Direction::schema().to_json_schema()["enum"], json!(["f", "b"]));
+}
+
+#[test]
+fn schema_test_path_rty_optional() {
+    use serde_json::json;
+
+    // the validator must accept the paths stored before `rty` existed
+    let s = Schema::document::<Path>();
+    assert_eq!(s["required"], json!(["id", "type", "sequence"]));
+    assert!(s["properties"]["rty"].is_object());
+}
+
+#[test]
+fn schema_test_segment_source_optional() {
+    use serde_json::json;
+
+    // the validator must accept the segments stored before `source` existed
+    let s = Schema::document::<Segment>();
+    assert_eq!(s["required"], json!(["from", "to", "type", "geometry"]));
+    assert!(s["properties"]["source"].is_object());
+}
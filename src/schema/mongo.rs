@@ -0,0 +1,80 @@
+use mongodb::{bson::{doc, Bson, Document}, options::CreateCollectionOptions, Database};
+
+use crate::BrussType;
+use super::{HasSchema, Schema};
+
+impl Schema {
+    /// Renders the schema as the body of a MongoDB `$jsonSchema` operator.
+    ///
+    /// MongoDB only supports a subset of draft 4, so types are checked with `bsonType` (there's
+    /// no `integer` type) and `format` isn't used.
+    pub fn to_bson_schema(&self) -> Document {
+        match self {
+            Schema::Integer { min, max } => {
+                let mut o = doc! { "bsonType": ["int", "long"] };
+                if let Some(min) = min {
+                    o.insert("minimum", *min);
+                }
+                if let Some(max) = max {
+                    o.insert("maximum", *max);
+                }
+                o
+            }
+            Schema::Number => doc! { "bsonType": ["double", "int", "long", "decimal"] },
+            Schema::String | Schema::DateTime => doc! { "bsonType": "string" },
+            Schema::Enum(values) => doc! { "bsonType": "string", "enum": values.clone() },
+            Schema::Boolean => doc! { "bsonType": "bool" },
+            Schema::BsonDateTime => doc! { "bsonType": "date" },
+            Schema::Nullable(inner) => doc! { "anyOf": [inner.to_bson_schema(), { "bsonType": "null" }] },
+            Schema::Array(items) => doc! { "bsonType": "array", "items": items.to_bson_schema() },
+            Schema::Tuple(items) => doc! {
+                "bsonType": "array",
+                "items": items.iter().map(|i| Bson::Document(i.to_bson_schema())).collect::<Vec<_>>(),
+                "minItems": items.len() as i64,
+                "maxItems": items.len() as i64,
+                "additionalItems": false,
+            },
+            Schema::Map { key, value } => {
+                let mut patterns = Document::new();
+                patterns.insert(*key, value.to_bson_schema());
+                doc! { "bsonType": "object", "patternProperties": patterns, "additionalProperties": false }
+            }
+            Schema::Object(fields) => {
+                let mut o = doc! { "bsonType": "object" };
+                let required = fields.iter().filter(|f| f.required).map(|f| f.name).collect::<Vec<_>>();
+                // an empty `required` is rejected by the server
+                if !required.is_empty() {
+                    o.insert("required", required);
+                }
+                o.insert("properties", fields.iter()
+                    .map(|f| (f.name.to_string(), Bson::Document(f.schema.to_bson_schema())))
+                    .collect::<Document>());
+                o
+            }
+            Schema::Named(_, inner) => inner.to_bson_schema(),
+        }
+    }
+}
+
+/// Installs the schema of `T` as the validator of its collection, creating the collection if it
+/// doesn't exist yet.
+///
+/// Validation is strict: any insert or update that produces a document not matching the schema is
+/// rejected by the server.
+pub async fn install_validator<T: BrussType + HasSchema>(db: &Database) -> mongodb::error::Result<()> {
+    let coll = T::TYPE.collection();
+    let validator = doc! { "$jsonSchema": T::schema().to_bson_schema() };
+    if db.list_collection_names(doc! { "name": coll }).await?.is_empty() {
+        let options = CreateCollectionOptions::builder()
+            .validator(Some(validator))
+            .build();
+        db.create_collection(coll, options).await
+    } else {
+        db.run_command(doc! {
+            "collMod": coll,
+            "validator": validator,
+            "validationLevel": "strict",
+            "validationAction": "error",
+        }, None).await.map(|_| ())
+    }
+}
@@ -0,0 +1,86 @@
+use super::Schema;
+
+const HEADER: &str = "\
+// This file is generated by bruss_data: do not edit it by hand.
+// Regenerate it with `BRUSS_UPDATE_BINDINGS=1 cargo test --features schema`.
+";
+
+impl Schema {
+    /// Renders the schema as a TypeScript type expression.
+    /// Named schemas are referenced by their name.
+    pub fn to_typescript(&self) -> String {
+        match self {
+            Schema::Integer { .. } | Schema::Number => "number".to_string(),
+            Schema::String | Schema::DateTime => "string".to_string(),
+            Schema::Enum(values) => values.iter()
+                .map(|v| format!("\"{}\"", v))
+                .collect::<Vec<_>>()
+                .join(" | "),
+            Schema::Boolean => "boolean".to_string(),
+            Schema::BsonDateTime => "{ $date: { $numberLong: string } }".to_string(),
+            Schema::Nullable(inner) => format!("{} | null", inner.to_typescript()),
+            Schema::Array(items) => {
+                let items = items.to_typescript();
+                if items.contains(' ') {
+                    format!("({})[]", items)
+                } else {
+                    format!("{}[]", items)
+                }
+            }
+            Schema::Tuple(items) => format!("[{}]", items.iter()
+                .map(Schema::to_typescript)
+                .collect::<Vec<_>>()
+                .join(", ")),
+            Schema::Map { value, .. } => format!("{{ [key: string]: {} }}", value.to_typescript()),
+            Schema::Object(fields) => format!("{{ {} }}", fields.iter()
+                .map(|f| format!("{}{}: {};", f.name, if f.required { "" } else { "?" }, f.schema.to_typescript()))
+                .collect::<Vec<_>>()
+                .join(" ")),
+            Schema::Named(name, _) => name.to_string(),
+        }
+    }
+
+    /// Renders a named schema as an exported declaration: objects become interfaces, everything
+    /// else a type alias.
+    pub fn to_typescript_declaration(&self) -> Option<String> {
+        let Schema::Named(name, inner) = self else {
+            return None
+        };
+        Some(match inner.as_ref() {
+            Schema::Object(fields) => {
+                let mut o = format!("export interface {} {{\n", name);
+                for f in fields {
+                    o.push_str(&format!("    {}{}: {};\n", f.name, if f.required { "" } else { "?" }, f.schema.to_typescript()));
+                }
+                o.push_str("}\n");
+                o
+            }
+            other => format!("export type {} = {};\n", name, other.to_typescript()),
+        })
+    }
+}
+
+/// Returns the content of the `.d.ts` file declaring all the bruss types.
+pub fn definitions() -> String {
+    let mut o = HEADER.to_string();
+    for s in super::all() {
+        if let Some(d) = s.to_typescript_declaration() {
+            o.push('\n');
+            o.push_str(&d);
+        }
+    }
+    o
+}
+
+#[cfg(feature = "schema")]
+#[test]
+fn typescript_test_bindings() {
+    const PATH: &str = concat!(env!("CARGO_MANIFEST_DIR"), "/bindings/bruss_data.d.ts");
+
+    let generated = definitions();
+    if std::env::var_os("BRUSS_UPDATE_BINDINGS").is_some() {
+        std::fs::write(PATH, &generated).unwrap();
+    }
+    let stored = std::fs::read_to_string(PATH).unwrap();
+    assert!(stored == generated, "{} is stale, regenerate it with `BRUSS_UPDATE_BINDINGS=1 cargo test --features schema`", PATH);
+}
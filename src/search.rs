@@ -0,0 +1,261 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+
+use crate::{AreaHelper, Coords, Stop, StopKey};
+
+/// Lowercases `s` and removes the accents of latin letters, e.g. `"Università"` becomes
+/// `"universita"`.
+pub fn fold(s: &str) -> String {
+    let mut o = String::with_capacity(s.len());
+    for c in s.chars().flat_map(char::to_lowercase) {
+        match c {
+            'à' | 'á' | 'â' | 'ã' | 'ä' | 'å' => o.push('a'),
+            'è' | 'é' | 'ê' | 'ë' => o.push('e'),
+            'ì' | 'í' | 'î' | 'ï' => o.push('i'),
+            'ò' | 'ó' | 'ô' | 'õ' | 'ö' => o.push('o'),
+            'ù' | 'ú' | 'û' | 'ü' => o.push('u'),
+            'ç' => o.push('c'),
+            'ñ' => o.push('n'),
+            'ß' => o.push_str("ss"),
+            // apostrophes join words in italian ("sant'ilario"), they separate them here
+            '\'' | '’' => o.push(' '),
+            c => o.push(c),
+        }
+    }
+    o
+}
+
+/// # Normalizer
+/// Turns stop fields and queries into comparable tokens: accents are folded, punctuation splits
+/// words and abbreviations are expanded, so `"Trento-p.zza Dante"` gives `trento piazza dante`.
+///
+/// Abbreviations are matched on whole words, dots included (`"p.zza"`), after folding.
+#[derive(Debug, Clone)]
+pub struct Normalizer {
+    abbreviations: HashMap<String, String>,
+}
+
+impl Default for Normalizer {
+    /// A normalizer with the abbreviations commonly found in TT stop names.
+    fn default() -> Self {
+        [
+            ("p.zza", "piazza"), ("p.za", "piazza"), ("p.le", "piazzale"),
+            ("v.", "via"), ("v.le", "viale"), ("c.so", "corso"), ("l.go", "largo"),
+            ("s.", "san"), ("ss.", "santissima"), ("staz.", "stazione"), ("osp.", "ospedale"),
+            ("loc.", "localita"), ("fraz.", "frazione"),
+        ]
+        .into_iter()
+        .fold(Self::new(), |n, (short, long)| n.abbreviation(short, long))
+    }
+}
+
+impl Normalizer {
+    /// A normalizer without abbreviations.
+    pub fn new() -> Self {
+        Self { abbreviations: HashMap::new() }
+    }
+
+    pub fn abbreviation(mut self, short: &str, long: &str) -> Self {
+        self.abbreviations.insert(fold(short), fold(long));
+        self
+    }
+
+    pub fn tokens(&self, s: &str) -> Vec<String> {
+        fold(s)
+            .split(|c: char| !c.is_alphanumeric() && c != '.')
+            .filter(|w| !w.is_empty())
+            .flat_map(|w| match self.abbreviations.get(w) {
+                Some(long) => long.split(' ').map(str::to_string).collect::<Vec<_>>(),
+                None => w.split('.').filter(|w| !w.is_empty()).map(str::to_string).collect(),
+            })
+            .collect()
+    }
+}
+
+/// A stop matching a query.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Hit {
+    pub stop: StopKey,
+    pub score: f64,
+}
+
+/// # StopQuery
+/// A search for stops, by free text and optionally near a position.
+#[derive(Debug, Clone)]
+pub struct StopQuery<'a> {
+    text: &'a str,
+    near: Option<Coords>,
+    limit: usize,
+}
+
+impl<'a> StopQuery<'a> {
+    pub const DEFAULT_LIMIT: usize = 10;
+
+    pub fn new(text: &'a str) -> Self {
+        Self { text, near: None, limit: Self::DEFAULT_LIMIT }
+    }
+
+    /// Boosts the stops close to `position`.
+    pub fn near(mut self, position: Coords) -> Self {
+        self.near = Some(position);
+        self
+    }
+
+    pub fn limit(mut self, limit: usize) -> Self {
+        self.limit = limit;
+        self
+    }
+}
+
+struct Entry {
+    key: StopKey,
+    position: Coords,
+    /// Tokens of each searchable field, with the weight of the field.
+    fields: Vec<(f64, Vec<String>)>,
+}
+
+/// # StopIndex
+/// In-memory full text index over the stops.
+///
+/// Every word of the query must match a word of the stop, either exactly, as a prefix or within a
+/// small edit distance (1 for words of at least 4 letters, 2 from 8 letters). The score of a stop
+/// is the mean of the best match of each word of the query, weighted by the field it's found in:
+/// the name counts the most, then the code, the street and town, and last the description.
+pub struct StopIndex {
+    normalizer: Normalizer,
+    entries: Vec<Entry>,
+    proximity: f64,
+}
+
+impl StopIndex {
+    /// Maximum score added to stops close to the position of a query.
+    pub const DEFAULT_PROXIMITY: f64 = 0.3;
+    /// Distance in meters at which the proximity boost is halved.
+    const PROXIMITY_HALF: f64 = 1000.;
+
+    pub fn new(stops: &AreaHelper<Stop>, normalizer: Normalizer) -> Self {
+        let mut entries = stops.values()
+            .map(|s| {
+                let mut fields = vec![
+                    (1., normalizer.tokens(&s.name)),
+                    (0.9, normalizer.tokens(&s.code)),
+                    (0.5, normalizer.tokens(&s.description)),
+                ];
+                fields.extend(s.street.as_deref().map(|f| (0.6, normalizer.tokens(f))));
+                fields.extend(s.town.as_deref().map(|f| (0.6, normalizer.tokens(f))));
+                Entry { key: s.key(), position: s.position.clone(), fields }
+            })
+            .collect::<Vec<_>>();
+        entries.sort_by_key(|e| e.key);
+        Self { normalizer, entries, proximity: Self::DEFAULT_PROXIMITY }
+    }
+
+    /// Sets the maximum score added to stops close to the position of a query.
+    pub fn proximity(mut self, boost: f64) -> Self {
+        self.proximity = boost;
+        self
+    }
+
+    /// Stops matching `query`, best first.
+    pub fn search(&self, query: &StopQuery) -> Vec<Hit> {
+        let words = self.normalizer.tokens(query.text);
+        if words.is_empty() {
+            return Vec::new();
+        }
+        let mut hits = self.entries.iter()
+            .filter_map(|e| {
+                let mut total = 0.;
+                for w in &words {
+                    let best = e.fields.iter()
+                        .flat_map(|(weight, tokens)| tokens.iter().map(move |t| weight * word_score(w, t)))
+                        .fold(0., f64::max);
+                    if best == 0. {
+                        return None;
+                    }
+                    total += best;
+                }
+                let mut score = total / words.len() as f64;
+                if let Some(near) = &query.near {
+                    score += self.proximity * Self::PROXIMITY_HALF / (Self::PROXIMITY_HALF + near.haversine(&e.position));
+                }
+                Some(Hit { stop: e.key, score })
+            })
+            .collect::<Vec<_>>();
+        hits.sort_by(|a, b| b.score.total_cmp(&a.score).then_with(|| a.stop.cmp(&b.stop)));
+        hits.truncate(query.limit);
+        hits
+    }
+}
+
+/// How well the query word `w` matches the stop word `t`, from 0 to 1.
+fn word_score(w: &str, t: &str) -> f64 {
+    if w == t {
+        return 1.;
+    }
+    if t.starts_with(w) {
+        return 0.8;
+    }
+    let max = match w.chars().count() {
+        0..=3 => return 0.,
+        4..=7 => 1,
+        _ => 2,
+    };
+    match edit_distance(w, t, max) {
+        Some(d) => 0.7 - 0.1 * d as f64,
+        None => 0.,
+    }
+}
+
+/// Levenshtein distance of `a` and `b`, `None` if it's greater than `max`.
+fn edit_distance(a: &str, b: &str, max: usize) -> Option<usize> {
+    let (a, b) = (a.chars().collect::<Vec<_>>(), b.chars().collect::<Vec<_>>());
+    if a.len().abs_diff(b.len()) > max {
+        return None;
+    }
+    let mut prev = (0..=b.len()).collect::<Vec<_>>();
+    let mut cur = vec![0; b.len() + 1];
+    for i in 1..=a.len() {
+        cur[0] = i;
+        for j in 1..=b.len() {
+            let cost = usize::from(a[i - 1] != b[j - 1]);
+            cur[j] = (prev[j] + 1).min(cur[j - 1] + 1).min(prev[j - 1] + cost);
+        }
+        if cur.iter().min().is_some_and(|m| *m > max) {
+            return None;
+        }
+        std::mem::swap(&mut prev, &mut cur);
+    }
+    Some(prev[b.len()]).filter(|d| *d <= max)
+}
+
+#[test]
+fn search_test_stops() {
+    use tt::AreaType;
+
+    let stop = |id, name, lat| Stop {
+        code: format!("{}", id),
+        town: Some("Trento".to_string()),
+        ..crate::test_util::stop(AreaType::U, id, name, Coords::new(lat, 11.12))
+    };
+    let stops = AreaHelper::from_iter([
+        stop(1, "Trento-p.zza Dante", 46.07),
+        stop(2, "Università", 46.06),
+        stop(3, "Dante Alighieri", 46.10),
+        stop(4, "Via Dante", 46.071),
+    ]);
+    let index = StopIndex::new(&stops, Normalizer::default());
+    let k = |id| StopKey::new(AreaType::U, id);
+    let first = |q: StopQuery| index.search(&q).first().map(|h| h.stop);
+
+    assert_eq!(Normalizer::default().tokens("Trento-p.zza Dante"), vec!["trento", "piazza", "dante"]);
+    assert_eq!(first(StopQuery::new("Trento Piazza Dante")), Some(k(1)));
+    assert_eq!(first(StopQuery::new("piaza dnte")), Some(k(1)));
+    assert_eq!(first(StopQuery::new("universita")), Some(k(2)));
+    assert_eq!(first(StopQuery::new("univ")), Some(k(2)));
+    assert!(index.search(&StopQuery::new("stazione")).is_empty());
+
+    // "dante" is in three names: the closest one wins
+    assert_eq!(index.search(&StopQuery::new("dante")).len(), 3);
+    assert_eq!(first(StopQuery::new("dante").near(Coords::new(46.10, 11.12))), Some(k(3)));
+}
@@ -0,0 +1,251 @@
+use std::collections::{BTreeSet, HashMap, HashSet};
+
+use serde::{Deserialize, Serialize};
+use tt::AreaType;
+use uuid::Uuid;
+
+use crate::{AreaHelper, BrussType, Coords, Normalizer, Stop, StopKey, Type};
+
+/// # Station
+/// A place made of several stops: the platforms of a bus station, or the two sides of a street.
+///
+/// `id` is kept across clusterings: a station takes the id most of its stops had in `Stop::station`,
+/// so it doesn't change when stops are added to or removed from it. A new station gets a UUIDv5 of
+/// its normalized name and first stop in the namespace of `Type::Station`. Stops know their
+/// station through `Stop::station`.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Station {
+    pub id: String,
+    pub name: String,
+    /// Mean position of the stops.
+    pub position: Coords,
+    /// Sorted stop keys. A station can contain both urban and extra-urban stops.
+    pub stops: Vec<StopKey>,
+}
+
+impl BrussType for Station {
+    const TYPE: Type = Type::Station;
+}
+
+impl Station {
+    pub fn contains(&self, stop: &StopKey) -> bool {
+        self.stops.binary_search(stop).is_ok()
+    }
+}
+
+/// # StationClustering
+/// Groups stops into stations: two stops are in the same station if they're closer than `radius`
+/// meters and their names are similar, directly or through other stops of the station. Stops
+/// aren't added to a station if that would put two of its stops further than `diameter` meters
+/// apart, so that a street lined with stops of the same name isn't a single station.
+///
+/// Names are compared on their tokens (see `Normalizer`): they're similar if the tokens of one are
+/// all in the other ("Autostazione" and "Trento Autostazione"), or if the share of common tokens
+/// is at least `similarity`.
+#[derive(Debug, Clone)]
+pub struct StationClustering {
+    radius: f64,
+    diameter: f64,
+    similarity: f64,
+    min_stops: usize,
+    normalizer: Normalizer,
+}
+
+impl Default for StationClustering {
+    fn default() -> Self {
+        Self { radius: Self::DEFAULT_RADIUS, diameter: Self::DEFAULT_DIAMETER, similarity: Self::DEFAULT_SIMILARITY, min_stops: 2, normalizer: Normalizer::default() }
+    }
+}
+
+impl StationClustering {
+    pub const DEFAULT_RADIUS: f64 = 150.;
+    pub const DEFAULT_DIAMETER: f64 = 300.;
+    pub const DEFAULT_SIMILARITY: f64 = 0.6;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn radius(mut self, meters: f64) -> Self {
+        self.radius = meters;
+        self
+    }
+
+    pub fn diameter(mut self, meters: f64) -> Self {
+        self.diameter = meters;
+        self
+    }
+
+    pub fn similarity(mut self, similarity: f64) -> Self {
+        self.similarity = similarity;
+        self
+    }
+
+    /// Groups with fewer stops than this don't become stations. Defaults to 2.
+    pub fn min_stops(mut self, min_stops: usize) -> Self {
+        self.min_stops = min_stops.max(1);
+        self
+    }
+
+    pub fn normalizer(mut self, normalizer: Normalizer) -> Self {
+        self.normalizer = normalizer;
+        self
+    }
+
+    fn similar(&self, a: &BTreeSet<String>, b: &BTreeSet<String>) -> bool {
+        if a.is_empty() || b.is_empty() {
+            return false;
+        }
+        let common = a.intersection(b).count();
+        common == a.len().min(b.len()) || common as f64 / a.union(b).count() as f64 >= self.similarity
+    }
+
+    /// Clusters `stops` into stations, setting the `station` of every stop (to `None` for the
+    /// stops that aren't part of any). Stations are sorted by id.
+    pub fn cluster(&self, stops: &mut AreaHelper<Stop>) -> Vec<Station> {
+        let mut list = stops.values()
+            .map(|s| (s.key(), s.position.clone(), s.name.clone(), self.normalizer.tokens(&s.name).into_iter().collect::<BTreeSet<_>>(), s.station.clone()))
+            .collect::<Vec<_>>();
+        list.sort_by(|a, b| a.1.lat.total_cmp(&b.1.lat).then_with(|| a.0.cmp(&b.0)));
+
+        let mut parent = (0..list.len()).collect::<Vec<_>>();
+        // stops of each group, by root
+        let mut members = (0..list.len()).map(|i| vec![i]).collect::<Vec<_>>();
+        // see `TransferGenerator::generate`
+        let window = self.radius / 111_000.;
+        for (i, a) in list.iter().enumerate() {
+            for (j, b) in list.iter().enumerate().skip(i + 1) {
+                if b.1.lat - a.1.lat > window {
+                    break;
+                }
+                if a.1.haversine(&b.1) > self.radius || !self.similar(&a.3, &b.3) {
+                    continue;
+                }
+                let (a, b) = (find(&mut parent, i), find(&mut parent, j));
+                if a == b {
+                    continue;
+                }
+                let fits = members[a].iter()
+                    .all(|x| members[b].iter().all(|y| list[*x].1.haversine(&list[*y].1) <= self.diameter));
+                if fits {
+                    let (root, child) = (a.min(b), a.max(b));
+                    parent[child] = root;
+                    let moved = std::mem::take(&mut members[child]);
+                    members[root].extend(moved);
+                }
+            }
+        }
+
+        for ty in [AreaType::U, AreaType::E] {
+            for s in stops.get_mut(ty).values_mut() {
+                s.station = None;
+            }
+        }
+        let mut groups = members.into_iter()
+            .filter(|g| !g.is_empty() && g.len() >= self.min_stops)
+            .map(|g| {
+                let mut keys = g.iter().map(|i| list[*i].0).collect::<Vec<_>>();
+                keys.sort();
+                (keys, g)
+            })
+            .collect::<Vec<_>>();
+        groups.sort_by(|a, b| a.0[0].cmp(&b.0[0]));
+        let mut taken = HashSet::new();
+        let mut stations = groups.into_iter()
+            .map(|(keys, g)| {
+                let n = g.len() as f64;
+                let position = Coords::new(
+                    g.iter().map(|i| list[*i].1.lat).sum::<f64>() / n,
+                    g.iter().map(|i| list[*i].1.lng).sum::<f64>() / n,
+                );
+                let name = station_name(g.iter().map(|i| list[*i].2.as_str()));
+                let id = previous_id(g.iter().filter_map(|i| list[*i].4.as_deref()), &taken)
+                    .unwrap_or_else(|| {
+                        let seed = format!("{}/{}", self.normalizer.tokens(&name).join(" "), keys[0]);
+                        Uuid::new_v5(&Type::Station.namespace(), seed.as_bytes()).to_string()
+                    });
+                taken.insert(id.clone());
+                Station { id, name, position, stops: keys }
+            })
+            .collect::<Vec<_>>();
+        stations.sort_by(|a, b| a.id.cmp(&b.id));
+        for st in &stations {
+            for k in &st.stops {
+                if let Some(s) = stops.get_mut(k.ty).get_mut(&k.id) {
+                    s.station = Some(st.id.clone());
+                }
+            }
+        }
+        stations
+    }
+}
+
+/// Union-find root of `i`, with path halving.
+fn find(parent: &mut [usize], mut i: usize) -> usize {
+    while parent[i] != i {
+        parent[i] = parent[parent[i]];
+        i = parent[i];
+    }
+    i
+}
+
+/// The station id most of the stops had, if no other station has taken it already.
+fn previous_id<'a>(ids: impl Iterator<Item = &'a str>, taken: &HashSet<String>) -> Option<String> {
+    let mut count: HashMap<&str, usize> = HashMap::new();
+    for id in ids.filter(|id| !taken.contains(*id)) {
+        *count.entry(id).or_default() += 1;
+    }
+    count.into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.cmp(a.0)))
+        .map(|(id, _)| id.to_string())
+}
+
+/// The most common name, the shortest one on ties.
+fn station_name<'a>(names: impl Iterator<Item = &'a str>) -> String {
+    let mut count: HashMap<&str, usize> = HashMap::new();
+    for n in names {
+        *count.entry(n).or_default() += 1;
+    }
+    count.into_iter()
+        .max_by(|a, b| a.1.cmp(&b.1).then_with(|| b.0.len().cmp(&a.0.len())).then_with(|| b.0.cmp(a.0)))
+        .map(|(n, _)| n.to_string())
+        .unwrap_or_default()
+}
+
+#[test]
+fn station_test_cluster() {
+    let stop = |id, name, lat, ty| crate::test_util::stop(ty, id, name, Coords::new(lat, 11.12));
+    let mut stops = AreaHelper::from_iter([
+        stop(1, "Trento Autostazione", 46.0700, AreaType::U),
+        stop(2, "Trento Autostazione", 46.0702, AreaType::U),
+        stop(3, "Autostazione", 46.0704, AreaType::E),
+        // close, but another name
+        stop(4, "Piazza Dante", 46.0703, AreaType::U),
+        // same name, but too far
+        stop(5, "Trento Autostazione", 46.0800, AreaType::U),
+    ]);
+    let stations = StationClustering::new().cluster(&mut stops);
+    assert_eq!(stations.len(), 1);
+    let s = &stations[0];
+    assert_eq!(s.name, "Trento Autostazione");
+    assert_eq!(s.stops.len(), 3);
+    assert!(s.contains(&StopKey::new(AreaType::E, 3)));
+    assert_eq!(stops.get(AreaType::E)[&3].station.as_ref(), Some(&s.id));
+    assert_eq!(stops.get(AreaType::U)[&4].station, None);
+}
+
+#[test]
+fn station_test_stable() {
+    let stop = |id, lat| crate::test_util::stop(AreaType::U, id, "Via Brennero", Coords::new(lat, 11.12));
+    // stops about 111m apart along the same street
+    let mut stops = AreaHelper::from_iter((1..=5).map(|i| stop(i, 46.070 + 0.001 * i as f64)));
+    let stations = StationClustering::new().cluster(&mut stops);
+    assert_eq!(stations.iter().map(|s| s.stops.len()).collect::<BTreeSet<_>>(), BTreeSet::from([2, 3]));
+    assert!(stations.iter().all(|s| s.stops.len() < 5));
+
+    let id = stops.get(AreaType::U)[&1].station.clone().unwrap();
+    let mut stops = AreaHelper::from_iter(stops.values().filter(|s| s.id != 1).cloned().chain([stop(6, 46.0715)]));
+    let stations = StationClustering::new().cluster(&mut stops);
+    assert!(stations.iter().any(|s| s.id == id && !s.contains(&StopKey::new(AreaType::U, 1))));
+    assert_eq!(stops.get(AreaType::U)[&2].station, Some(id));
+}
@@ -1,3 +1,5 @@
+use std::{cmp::Ordering, fmt::Display, hash::Hash};
+
 use serde::{Serialize,Deserialize};
 use tt::{TTStop,AreaType};
 use crate::Type;
@@ -19,12 +21,19 @@ pub struct Stop {
     pub town: Option<String>,
     #[serde(rename = "type")]
     pub ty: AreaType,
-    pub wheelchair_boarding: bool
+    pub wheelchair_boarding: bool,
+    /// Id of the `Station` the stop is part of, if any.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub station: Option<String>,
 }
 
 impl Stop {
+    pub fn key(&self) -> StopKey {
+        StopKey::from(self)
+    }
+
     pub fn new(id: u16, code: String, description: String, position: Coords, altitude: i32, name: String, street: Option<String>, town: Option<String>, ty: AreaType, wheelchair_boarding: bool) -> Self {
-        Self { id, code, description, position, altitude, name, street, town, ty, wheelchair_boarding }
+        Self { id, code, description, position, altitude, name, street, town, ty, wheelchair_boarding, station: None }
     }
 }
 
@@ -35,7 +44,7 @@ impl BrussType for Stop {
 impl FromTT<TTStop> for Stop {
     fn from_tt(value: TTStop) -> Self {
         let TTStop { id, code, description, lat, lng, altitude, name, street, town, ty, wheelchair_boarding } = value;
-        Self { id, code, description, position: Coords::new(lat, lng), altitude, name, street, town, ty, wheelchair_boarding }
+        Self { id, code, description, position: Coords::new(lat, lng), altitude, name, street, town, ty, wheelchair_boarding, station: None }
     }
 }
 
@@ -51,3 +60,60 @@ impl InArea for Stop {
 
 pub type StopPair = (u16, u16);
 
+/// # StopKey
+/// Identifies a stop across areas: stop ids are only unique inside an area type.
+#[derive(Serialize, Deserialize, Debug, Clone, Copy)]
+pub struct StopKey {
+    #[serde(rename = "type")]
+    pub ty: AreaType,
+    pub id: u16,
+}
+
+impl StopKey {
+    pub fn new(ty: AreaType, id: u16) -> Self {
+        Self { ty, id }
+    }
+
+    fn as_tuple(&self) -> (u8, u16) {
+        (self.ty.into(), self.id)
+    }
+}
+
+impl From<&Stop> for StopKey {
+    fn from(value: &Stop) -> Self {
+        Self { ty: value.ty, id: value.id }
+    }
+}
+
+impl PartialEq for StopKey {
+    fn eq(&self, other: &Self) -> bool {
+        self.as_tuple() == other.as_tuple()
+    }
+}
+
+impl Eq for StopKey {}
+
+impl Hash for StopKey {
+    fn hash<H: std::hash::Hasher>(&self, state: &mut H) {
+        self.as_tuple().hash(state);
+    }
+}
+
+impl PartialOrd for StopKey {
+    fn partial_cmp(&self, other: &Self) -> Option<Ordering> {
+        Some(self.cmp(other))
+    }
+}
+
+impl Ord for StopKey {
+    fn cmp(&self, other: &Self) -> Ordering {
+        self.as_tuple().cmp(&other.as_tuple())
+    }
+}
+
+impl Display for StopKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "{:?}{}", self.ty, self.id)
+    }
+}
+
@@ -0,0 +1,123 @@
+use std::collections::{BTreeMap, BTreeSet};
+
+use serde::{Deserialize, Serialize};
+use tt::AreaType;
+
+use crate::{AreaHelper, BrussType, Dataset, Direction, InArea, Path, Trip, Type};
+
+/// A route serving a stop in one direction.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct ServedRoute {
+    pub route: u16,
+    pub direction: Direction,
+    /// Sorted headsigns of the trips stopping here.
+    pub headsigns: Vec<String>,
+    pub trips: u32,
+    /// The stop is the last one of all the trips: they only arrive here.
+    pub terminus: bool,
+}
+
+/// # StopRoutes
+/// The routes serving a stop, with their directions and headsigns. It's the precomputed answer
+/// to "where can I go from here", stored in its own collection with the same id and type of the
+/// stop it refers to.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct StopRoutes {
+    pub id: u16,
+    #[serde(rename = "type")]
+    pub ty: AreaType,
+    /// Sorted by route, forward direction first.
+    pub routes: Vec<ServedRoute>,
+}
+
+impl BrussType for StopRoutes {
+    const TYPE: Type = Type::StopRoutes;
+}
+
+impl InArea for StopRoutes {
+    fn ty(&self) -> AreaType {
+        self.ty
+    }
+
+    fn id(&self) -> u16 {
+        self.id
+    }
+}
+
+#[derive(Default)]
+struct Served {
+    headsigns: BTreeSet<String>,
+    trips: u32,
+    terminus: bool,
+}
+
+impl StopRoutes {
+    /// Builds the index of all the stops served by `trips`, finding the path of each trip with
+    /// `path_of`. Trips whose path can't be found are skipped.
+    pub fn build<'a>(trips: impl IntoIterator<Item = &'a Trip>, path_of: impl Fn(&str) -> Option<&'a Path>) -> AreaHelper<StopRoutes> {
+        let mut served: BTreeMap<(u8, u16), (AreaType, BTreeMap<(u16, u8), (Direction, Served)>)> = BTreeMap::new();
+        for t in trips {
+            let Some(path) = path_of(&t.path) else { continue };
+            for (i, stop) in path.sequence.iter().enumerate() {
+                let last = i + 1 == path.sequence.len();
+                let (_, routes) = served.entry((path.ty.into(), *stop)).or_insert_with(|| (path.ty, BTreeMap::new()));
+                let (_, s) = routes.entry((t.route, matches!(t.direction, Direction::Backward) as u8))
+                    .or_insert_with(|| (t.direction.clone(), Served { terminus: true, ..Default::default() }));
+                s.headsigns.insert(t.headsign.clone());
+                s.trips += 1;
+                s.terminus &= last;
+            }
+        }
+        served.into_iter()
+            .map(|((_, id), (ty, routes))| StopRoutes {
+                id,
+                ty,
+                routes: routes.into_iter()
+                    .map(|((route, _), (direction, s))| ServedRoute {
+                        route,
+                        direction,
+                        headsigns: s.headsigns.into_iter().collect(),
+                        trips: s.trips,
+                        terminus: s.terminus,
+                    })
+                    .collect(),
+            })
+            .collect()
+    }
+
+    pub fn from_dataset(dataset: &Dataset) -> AreaHelper<StopRoutes> {
+        Self::build(dataset.trips(), |id| dataset.path(id))
+    }
+
+    /// Routes from which one can board here.
+    pub fn departing(&self) -> impl Iterator<Item = &ServedRoute> {
+        self.routes.iter().filter(|r| !r.terminus)
+    }
+}
+
+#[test]
+fn stop_routes_test_build() {
+    use std::collections::HashMap;
+    use crate::{test_util, RoutingType};
+
+    let forward = Path::new(vec![1, 2, 3], AreaType::U, RoutingType::Bus);
+    let backward = Path::new(vec![3, 2, 1], AreaType::U, RoutingType::Bus);
+    let trip = |id: &str, direction, headsign: &str, path: &Path| Trip { direction, headsign: headsign.to_string(), ..test_util::trip(id, 5, path) };
+    let trips = [
+        trip("a", Direction::Forward, "Centro", &forward),
+        trip("b", Direction::Forward, "Centro via Dante", &forward),
+        trip("c", Direction::Backward, "Stazione", &backward),
+    ];
+    let paths = HashMap::from([(forward.id.clone(), &forward), (backward.id.clone(), &backward)]);
+    let index = StopRoutes::build(&trips, |id| paths.get(id).copied());
+
+    let middle = &index.get(AreaType::U)[&2];
+    assert_eq!(middle.routes.len(), 2);
+    assert_eq!(middle.routes[0].direction, Direction::Forward);
+    assert_eq!(middle.routes[0].headsigns, vec!["Centro", "Centro via Dante"]);
+    assert_eq!(middle.routes[0].trips, 2);
+
+    let end = &index.get(AreaType::U)[&3];
+    assert!(end.routes[0].terminus);
+    assert_eq!(end.departing().map(|r| r.direction.clone()).collect::<Vec<_>>(), vec![Direction::Backward]);
+}
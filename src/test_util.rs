@@ -0,0 +1,49 @@
+//! Valid records to build test fixtures from.
+
+use std::collections::HashMap;
+
+use chrono::TimeDelta;
+use serde_json::json;
+use tt::{AreaType, TTTrip};
+
+use crate::{Coords, Direction, Path, Stop, StopTime, Trip};
+
+/// A stop with only a name and a position, accessible by wheelchair.
+pub(crate) fn stop(ty: AreaType, id: u16, name: &str, position: Coords) -> Stop {
+    Stop::new(id, String::new(), String::new(), position, 0, name.to_string(), None, None, ty, true)
+}
+
+/// A forward trip of `route` along `path`, reaching a stop every 5 minutes. Like `Trip::from_tt`,
+/// it keeps the times of the last visit of the stops the path passes by more than once.
+pub(crate) fn trip(id: &str, route: u16, path: &Path) -> Trip {
+    let times = path.sequence.iter().enumerate()
+        .map(|(i, s)| (*s, StopTime { arrival: TimeDelta::minutes(5 * i as i64), departure: TimeDelta::minutes(5 * i as i64) }))
+        .collect::<HashMap<_, _>>();
+    Trip::new(id.to_string(), 0, Direction::Forward, 0, 0, None, route, String::new(), path.id.clone(), times, path.ty, None)
+}
+
+/// A TT trip of `route` stopping at `stops`, one minute apart from `start` (`HH:MM`).
+pub(crate) fn tt_trip(id: &str, route: u16, stops: &[u16], start: &str) -> TTTrip {
+    let (h, m) = start.split_once(':').unwrap();
+    let start = h.parse::<u32>().unwrap() * 60 + m.parse::<u32>().unwrap();
+    let stop_times = stops.iter().enumerate()
+        .map(|(i, s)| {
+            let t = start + i as u32;
+            let t = format!("{:02}:{:02}:00", t / 60, t % 60);
+            json!({ "arrivalTime": t, "departureTime": t, "stopId": s, "stopSequence": i + 1, "tripId": id, "type": "U" })
+        })
+        .collect::<Vec<_>>();
+    serde_json::from_value(json!({
+        "tripId": id,
+        "delay": null,
+        "directionId": 0,
+        "stopNext": 0,
+        "stopLast": 0,
+        "matricolaBus": null,
+        "routeId": route,
+        "stopTimes": stop_times,
+        "type": "U",
+        "tripHeadsign": "",
+        "lastEventRecivedAt": null,
+    })).unwrap()
+}
@@ -0,0 +1,288 @@
+use std::fmt::Write;
+
+use chrono::{DateTime, FixedOffset, NaiveDate, Offset, TimeDelta, TimeZone};
+use serde::Serialize;
+
+use crate::{map::align, Dataset, Direction, Path, StopKey};
+
+/// A cell of the timetable: the time a trip leaves a stop.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+#[serde(tag = "kind", rename_all = "snake_case")]
+pub enum TimetableCell {
+    Time { arrival: DateTime<FixedOffset>, departure: DateTime<FixedOffset> },
+    /// The trip passes by the stop without stopping, or it's an earlier visit of a stop the trip
+    /// passes by more than once: a trip only has the times of its last visit of each stop.
+    Skipped,
+    /// The stop is before the start or after the end of the trip.
+    NotServed,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TimetableStop {
+    pub stop: StopKey,
+    /// Empty if the stop is unknown.
+    pub name: String,
+}
+
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct TimetableTrip {
+    pub trip: String,
+    pub headsign: String,
+    pub departure: DateTime<FixedOffset>,
+}
+
+/// # Timetable
+/// The trips of a route in one direction on a service date, as a stops × trips matrix: `cells`
+/// has a row for each stop and a column for each trip.
+///
+/// Stops are in the order given by merging the paths of all the trips (see
+/// `Path::merge_sequences`), trips are sorted by departure. It serializes to JSON as is, and it can
+/// be rendered as CSV and as an HTML table for printing.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Timetable {
+    pub route: u16,
+    pub code: String,
+    pub name: String,
+    pub color: String,
+    pub direction: Direction,
+    pub date: NaiveDate,
+    pub stops: Vec<TimetableStop>,
+    pub trips: Vec<TimetableTrip>,
+    pub cells: Vec<Vec<TimetableCell>>,
+}
+
+/// Hours after midnight before which departures belong to the previous service date, like the
+/// times of `Trip::from_tt`.
+const DAY_START: i64 = 4;
+
+/// # TimetableQuery
+/// The timetable of a route in a direction on a service date. Times are shown in the `offset`
+/// time zone (UTC by default), which also decides which day a departure belongs to: the service
+/// date runs from 04:00 to 04:00 of the next day, so trips after midnight are in the timetable of
+/// the day before.
+#[derive(Debug, Clone)]
+pub struct TimetableQuery {
+    route: u16,
+    direction: Direction,
+    date: NaiveDate,
+    offset: FixedOffset,
+}
+
+impl TimetableQuery {
+    pub fn new(route: u16, direction: Direction, date: NaiveDate) -> Self {
+        Self { route, direction, date, offset: FixedOffset::east_opt(0).unwrap() }
+    }
+
+    /// The offset must be the one in effect on the service date: see `zone` to get it from a time
+    /// zone.
+    pub fn offset(mut self, offset: FixedOffset) -> Self {
+        self.offset = offset;
+        self
+    }
+
+    /// Shows times in `tz` (e.g. `chrono_tz::Europe::Rome`), with the offset in effect at noon of
+    /// the service date.
+    pub fn zone<Tz: TimeZone>(mut self, tz: &Tz) -> Self {
+        self.offset = tz.offset_from_utc_datetime(&self.date.and_hms_opt(12, 0, 0).unwrap()).fix();
+        self
+    }
+
+    /// Builds the timetable from the trips and schedules of `dataset`. Returns `None` if the route
+    /// doesn't exist. Trips whose path is unknown or isn't in the area type of the route are left
+    /// out, so that all the stops are in the same area.
+    pub fn build(&self, dataset: &Dataset) -> Option<Timetable> {
+        let route = dataset.route(self.route)?;
+        let mut columns = dataset.trips_of_route(self.route)
+            .filter(|t| t.direction == self.direction)
+            .filter_map(|t| Some((t, dataset.path_of(t)?)))
+            .filter(|(_, p)| p.ty == route.area_ty)
+            .flat_map(|(t, p)| dataset.schedules_of_trip(&t.id).map(move |s| (t, p, s)))
+            .filter(|(_, _, s)| (s.departure.with_timezone(&self.offset) - TimeDelta::hours(DAY_START)).date_naive() == self.date)
+            .collect::<Vec<_>>();
+        columns.sort_by(|a, b| a.2.departure.cmp(&b.2.departure).then_with(|| a.0.id.cmp(&b.0.id)));
+
+        // each path is merged once, however many trips follow it
+        let sequence = Path::merge_sequences(columns.iter().map(|(_, p, _)| p.sequence.as_slice()));
+        let stops = sequence.iter()
+            .map(|id| {
+                let stop = StopKey::new(route.area_ty, *id);
+                TimetableStop { stop, name: dataset.stop(&stop).map(|s| s.name.clone()).unwrap_or_default() }
+            })
+            .collect();
+
+        let mut cells = vec![Vec::new(); sequence.len()];
+        for (_, path, schedule) in &columns {
+            let rows = align(&sequence, &path.sequence);
+            let (first, last) = (rows.first().copied().unwrap_or(0), rows.last().copied().unwrap_or(0));
+            let timed = rows.iter().enumerate()
+                .filter(|(i, _)| !path.sequence[i + 1..].contains(&path.sequence[*i]))
+                .map(|(_, r)| *r)
+                .collect::<Vec<_>>();
+            for (r, row) in cells.iter_mut().enumerate() {
+                let time = timed.binary_search(&r).ok()
+                    .and_then(|_| schedule.hints.times.get(&sequence[r]));
+                row.push(match time {
+                    Some(t) => TimetableCell::Time {
+                        arrival: (schedule.departure + t.arrival).with_timezone(&self.offset),
+                        departure: (schedule.departure + t.departure).with_timezone(&self.offset),
+                    },
+                    None if (r > first && r < last) || rows.contains(&r) => TimetableCell::Skipped,
+                    None => TimetableCell::NotServed,
+                });
+            }
+        }
+
+        Some(Timetable {
+            route: route.id,
+            code: route.code.clone(),
+            name: route.name.clone(),
+            color: route.color.clone(),
+            direction: self.direction.clone(),
+            date: self.date,
+            stops,
+            trips: columns.iter()
+                .map(|(t, _, s)| TimetableTrip { trip: t.id.clone(), headsign: t.headsign.clone(), departure: s.departure.with_timezone(&self.offset) })
+                .collect(),
+            cells,
+        })
+    }
+}
+
+impl TimetableCell {
+    /// The text printed in the cell: the departure time, `|` for skipped stops and nothing for
+    /// stops outside of the trip.
+    pub fn text(&self) -> String {
+        match self {
+            Self::Time { departure, .. } => departure.format("%H:%M").to_string(),
+            Self::Skipped => "|".to_string(),
+            Self::NotServed => String::new(),
+        }
+    }
+}
+
+impl Timetable {
+    /// One line per stop, with its code and name followed by the cells; the header holds the
+    /// trip headsigns.
+    pub fn to_csv(&self) -> String {
+        let mut o = String::new();
+        let header = ["stop".to_string(), "name".to_string()].into_iter()
+            .chain(self.trips.iter().map(|t| t.headsign.clone()));
+        csv_line(&mut o, header);
+        for (stop, row) in self.stops.iter().zip(&self.cells) {
+            let line = [stop.stop.to_string(), stop.name.clone()].into_iter()
+                .chain(row.iter().map(TimetableCell::text));
+            csv_line(&mut o, line);
+        }
+        o
+    }
+
+    /// A standalone `<table>`, with the route as caption and a class on each cell (`time`, `skip`
+    /// or `none`) for styling.
+    pub fn to_html(&self) -> String {
+        let mut o = String::new();
+        let direction = self.trips.first().map(|t| t.headsign.as_str()).unwrap_or_default();
+        let _ = writeln!(o, "<table class=\"timetable\" data-route=\"{}\">", self.route);
+        let _ = writeln!(o, "<caption><span class=\"route\" style=\"background: #{}\">{}</span> {} &rarr; {} &middot; {}</caption>",
+            escape(&self.color), escape(&self.code), escape(&self.name), escape(direction), self.date);
+        o.push_str("<thead><tr><th></th>");
+        for t in &self.trips {
+            let _ = write!(o, "<th>{}</th>", escape(&t.headsign));
+        }
+        o.push_str("</tr></thead>\n<tbody>\n");
+        for (stop, row) in self.stops.iter().zip(&self.cells) {
+            let _ = write!(o, "<tr><th>{}</th>", escape(&stop.name));
+            for c in row {
+                let class = match c {
+                    TimetableCell::Time { .. } => "time",
+                    TimetableCell::Skipped => "skip",
+                    TimetableCell::NotServed => "none",
+                };
+                let _ = write!(o, "<td class=\"{}\">{}</td>", class, c.text());
+            }
+            o.push_str("</tr>\n");
+        }
+        o.push_str("</tbody>\n</table>\n");
+        o
+    }
+}
+
+fn csv_line(o: &mut String, fields: impl Iterator<Item = String>) {
+    let fields = fields
+        .map(|f| if f.contains([',', '"', '\n']) { format!("\"{}\"", f.replace('"', "\"\"")) } else { f })
+        .collect::<Vec<_>>();
+    o.push_str(&fields.join(","));
+    o.push('\n');
+}
+
+fn escape(s: &str) -> String {
+    s.replace('&', "&amp;").replace('<', "&lt;").replace('>', "&gt;").replace('"', "&quot;")
+}
+
+#[test]
+fn timetable_test_build() {
+    use chrono::Utc;
+    use tt::AreaType;
+    use crate::{test_util, Coords, Route, RoutingType, Schedule, Trip};
+
+    let stop = |id, name| test_util::stop(AreaType::U, id, name, Coords::new(46.07, 11.12));
+    let full = Path::new(vec![1, 2, 3, 4], AreaType::U, RoutingType::Bus);
+    let express = Path::new(vec![1, 3, 4], AreaType::U, RoutingType::Bus);
+    let short = Path::new(vec![1, 2], AreaType::U, RoutingType::Bus);
+    let trip = |id: &str, path: &Path| Trip { headsign: format!("to {}", id), ..test_util::trip(id, 5, path) };
+    // a path of another area, with the same stop ids
+    let other = Path::new(vec![1, 2], AreaType::E, RoutingType::Railway);
+    let trips = [trip("a", &full), trip("b", &express), trip("c", &short), trip("d", &other)];
+    let at = |d, h| Utc.with_ymd_and_hms(2024, 5, d, h, 0, 0).unwrap();
+
+    let mut d = Dataset::new();
+    d.add_stops([stop(1, "Piazza Dante"), stop(2, "Via Roma, 3"), stop(3, "Duomo"), stop(4, "Ospedale")]);
+    d.add_routes([Route::new(5, 3, 1, AreaType::U, "ff0000".to_string(), "Centro".to_string(), "5".to_string())]);
+    d.add_schedules([
+        Schedule::from_trip(&trips[1], at(1, 9)).unwrap(),
+        Schedule::from_trip(&trips[0], at(1, 8)).unwrap(),
+        Schedule::from_trip(&trips[2], at(1, 10)).unwrap(),
+        Schedule::from_trip(&trips[0], at(2, 8)).unwrap(),
+        // after midnight, still on May 1st
+        Schedule::from_trip(&trips[2], at(2, 1)).unwrap(),
+        Schedule::from_trip(&trips[3], at(1, 11)).unwrap(),
+    ]);
+    d.add_trips(trips);
+    d.add_paths([full, express, short, other]);
+
+    let date = NaiveDate::from_ymd_opt(2024, 5, 1).unwrap();
+    let t = TimetableQuery::new(5, Direction::Forward, date).build(&d).unwrap();
+    assert_eq!(t.stops.iter().map(|s| s.stop.id).collect::<Vec<_>>(), vec![1, 2, 3, 4]);
+    assert_eq!(t.trips.iter().map(|t| t.trip.as_str()).collect::<Vec<_>>(), vec!["a", "b", "c", "c"]);
+    assert_eq!(t.cells[1][1], TimetableCell::Skipped);
+    assert_eq!(t.cells[2][2], TimetableCell::NotServed);
+    assert_eq!(t.cells[2][1].text(), "09:05");
+
+    let csv = t.to_csv();
+    assert_eq!(csv.lines().nth(2), Some("U2,\"Via Roma, 3\",08:05,|,10:05,01:05"));
+    assert!(t.to_html().contains("<td class=\"skip\">|</td>"));
+    assert!(TimetableQuery::new(6, Direction::Forward, date).build(&d).is_none());
+
+    let t = TimetableQuery::new(5, Direction::Forward, date).zone(&FixedOffset::east_opt(2 * 3600).unwrap()).build(&d).unwrap();
+    assert_eq!(t.cells[0].iter().map(TimetableCell::text).collect::<Vec<_>>(), vec!["10:00", "11:00", "12:00", "03:00"]);
+}
+
+#[test]
+fn timetable_test_loop() {
+    use chrono::Utc;
+    use tt::AreaType;
+    use crate::{test_util::trip, Route, RoutingType, Schedule};
+
+    let path = Path::new(vec![1, 2, 3, 1], AreaType::U, RoutingType::Bus);
+    // stop 1 keeps the time of the second visit
+    let trip = trip("a", 5, &path);
+
+    let mut d = Dataset::new();
+    d.add_routes([Route::new(5, 3, 1, AreaType::U, String::new(), String::new(), "5".to_string())]);
+    d.add_schedules([Schedule::from_trip(&trip, Utc.with_ymd_and_hms(2024, 5, 1, 8, 0, 0).unwrap()).unwrap()]);
+    d.add_trips([trip]);
+    d.add_paths([path]);
+
+    let t = TimetableQuery::new(5, Direction::Forward, NaiveDate::from_ymd_opt(2024, 5, 1).unwrap()).build(&d).unwrap();
+    assert_eq!(t.stops.iter().map(|s| s.stop.id).collect::<Vec<_>>(), vec![1, 2, 3, 1]);
+    assert_eq!(t.cells.iter().map(|r| r[0].text()).collect::<Vec<_>>(), vec!["|", "08:05", "08:10", "08:15"]);
+}
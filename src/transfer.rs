@@ -0,0 +1,144 @@
+use chrono::TimeDelta;
+use serde::{Deserialize, Serialize};
+
+use crate::{AreaHelper, BrussType, Coords, Stop, StopKey, Type};
+
+/// # Transfer
+/// A walking connection from a stop to a nearby one, possibly of another area type.
+///
+/// Transfers are directed: walking uphill takes longer than walking downhill, so the two
+/// directions usually have different durations.
+#[derive(Serialize, Deserialize, Debug, Clone, PartialEq)]
+pub struct Transfer {
+    pub from: StopKey,
+    pub to: StopKey,
+    /// Estimated walking distance in meters.
+    pub distance: f64,
+    pub duration: TimeDelta,
+}
+
+impl BrussType for Transfer {
+    const TYPE: Type = Type::Transfer;
+}
+
+/// # TransferGenerator
+/// Generates the transfers between all the stops closer than `radius` meters.
+///
+/// The walking distance is the straight line distance multiplied by `detour`, to account for
+/// streets not being straight. Walking speed on flat ground is `speed`, and it's adjusted on the
+/// slope given by the altitude of the stops with Tobler's hiking function: it's a bit faster
+/// downhill and much slower uphill.
+#[derive(Clone, Copy, Debug)]
+pub struct TransferGenerator {
+    radius: f64,
+    speed: f64,
+    detour: f64,
+}
+
+impl Default for TransferGenerator {
+    fn default() -> Self {
+        Self { radius: Self::DEFAULT_RADIUS, speed: Self::DEFAULT_SPEED, detour: Self::DEFAULT_DETOUR }
+    }
+}
+
+impl TransferGenerator {
+    pub const DEFAULT_RADIUS: f64 = 300.;
+    /// Meters per second, about 4.7 km/h.
+    pub const DEFAULT_SPEED: f64 = 1.3;
+    pub const DEFAULT_DETOUR: f64 = 1.3;
+
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    pub fn radius(mut self, meters: f64) -> Self {
+        self.radius = meters;
+        self
+    }
+
+    /// Walking speed on flat ground, in meters per second.
+    pub fn speed(mut self, speed: f64) -> Self {
+        assert!(speed > 0., "speed must be positive");
+        self.speed = speed;
+        self
+    }
+
+    pub fn detour(mut self, detour: f64) -> Self {
+        assert!(detour >= 1., "detour can't be less than 1");
+        self.detour = detour;
+        self
+    }
+
+    /// Time to walk `distance` meters climbing `climb` meters (negative when going downhill).
+    pub fn duration(&self, distance: f64, climb: f64) -> TimeDelta {
+        let slope = if distance > 0. { climb / distance } else { 0. };
+        // Tobler's function, scaled so that the speed on flat ground is `self.speed`
+        let speed = self.speed * (-3.5 * ((slope + 0.05).abs() - 0.05)).exp();
+        TimeDelta::seconds((distance / speed).round() as i64)
+    }
+
+    /// Time to walk from `from` to `to` on flat ground.
+    pub fn walk_time(&self, from: &Coords, to: &Coords) -> TimeDelta {
+        self.duration(from.haversine(to) * self.detour, 0.)
+    }
+
+    /// Straight line distance in meters that can be covered walking on flat ground in `time`.
+    pub fn reach(&self, time: TimeDelta) -> f64 {
+        time.num_seconds().max(0) as f64 * self.speed / self.detour
+    }
+
+    pub fn transfer(&self, from: &Stop, to: &Stop) -> Transfer {
+        let distance = from.position.haversine(&to.position) * self.detour;
+        Transfer {
+            from: from.key(),
+            to: to.key(),
+            distance,
+            duration: self.duration(distance, (to.altitude - from.altitude) as f64),
+        }
+    }
+
+    /// Transfers in both directions between all the pairs of stops within `radius`, sorted by
+    /// origin and destination.
+    pub fn generate(&self, stops: &AreaHelper<Stop>) -> Vec<Transfer> {
+        let mut stops = stops.values().collect::<Vec<_>>();
+        stops.sort_by(|a, b| a.position.lat.total_cmp(&b.position.lat));
+        // a degree of latitude is always about 111km, so stops farther than this in latitude are
+        // surely out of the radius
+        let window = self.radius / 111_000.;
+        let mut o = Vec::new();
+        for (i, a) in stops.iter().enumerate() {
+            for b in stops[i + 1..].iter().take_while(|b| b.position.lat - a.position.lat <= window) {
+                if a.position.haversine(&b.position) <= self.radius {
+                    o.push(self.transfer(a, b));
+                    o.push(self.transfer(b, a));
+                }
+            }
+        }
+        o.sort_by_key(|t| (t.from, t.to));
+        o
+    }
+}
+
+#[test]
+fn transfer_test_generate() {
+    use tt::AreaType;
+    use crate::test_util;
+
+    let stop = |id, ty, lat, altitude| Stop { altitude, ..test_util::stop(ty, id, "", Coords::new(lat, 11.12)) };
+    let stops = AreaHelper::from_iter([
+        stop(1, AreaType::U, 46.0700, 200),
+        // about 100m north, 10m higher
+        stop(7, AreaType::E, 46.0709, 210),
+        stop(2, AreaType::U, 46.0800, 200),
+    ]);
+    let transfers = TransferGenerator::new().generate(&stops);
+    assert_eq!(transfers.len(), 2);
+    let up = transfers.iter().find(|t| t.from == StopKey::new(AreaType::U, 1)).unwrap();
+    let down = transfers.iter().find(|t| t.from == StopKey::new(AreaType::E, 7)).unwrap();
+    assert_eq!(up.to, StopKey::new(AreaType::E, 7));
+    assert!((up.distance - 130.).abs() < 1., "{}", up.distance);
+    assert!(up.duration > down.duration);
+
+    let flat = TransferGenerator::new().duration(130., 0.);
+    assert_eq!(flat, TimeDelta::seconds(100));
+}
@@ -4,7 +4,7 @@ use chrono::{TimeDelta, Utc, DateTime};
 use serde::{Deserialize, Serialize};
 use tt::{TTTrip, AreaType};
 
-use crate::{sequence_hash, stop_time::{StopTime, StopTimes}, BrussType, Type};
+use crate::{sequence_uuid, stop_time::{StopTime, StopTimes}, BrussType, Type};
 
 #[derive(Serialize,Deserialize,Debug,PartialEq,Clone)]
 pub enum Direction {
@@ -74,6 +74,24 @@ impl Display for Direction {
     }
 }
 
+#[derive(Debug)]
+pub enum TripError {
+    /// The trip has no stop time with sequence 1, so its departure time is unknown.
+    NoFirstStop { trip: String },
+    Direction { trip: String, value: u16 },
+}
+
+impl std::error::Error for TripError {}
+
+impl Display for TripError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::NoFirstStop { trip } => write!(f, "trip {} has no stop time with sequence 1", trip),
+            Self::Direction { trip, value } => write!(f, "trip {} has unrecognized direction {}", trip, value),
+        }
+    }
+}
+
 #[derive(Serialize,Deserialize,Debug)]
 pub struct Trip {
     pub id: String,
@@ -139,12 +157,33 @@ impl BrussType for Trip {
 
 impl Trip {
     pub fn from_tt(value: TTTrip) -> (Self, TimeDelta) {
+        let (trip, dep, _) = Self::from_tt_with_sequence(value);
+        (trip, dep)
+    }
+
+    /// Like `from_tt`, but it also returns the sequence of stops the trip follows, from which
+    /// `path` has been computed.
+    pub fn from_tt_with_sequence(value: TTTrip) -> (Self, TimeDelta, Vec<u16>) {
+        match Self::try_from_tt_with_sequence(value) {
+            Ok(v) => v,
+            Err(e) => panic!("{}", e)
+        }
+    }
+
+    /// Fallible version of `from_tt_with_sequence`, for trips coming from untrusted dumps.
+    pub fn try_from_tt_with_sequence(value: TTTrip) -> Result<(Self, TimeDelta, Vec<u16>), TripError> {
         let TTTrip { id, delay, direction, next_stop, last_stop, bus_id, route, stop_times, ty, headsign, last_event } = value;
+        if direction > 1 {
+            return Err(TripError::Direction { trip: id, value: direction });
+        }
         let mut times = HashMap::new();
         // this usually takes O(1) since usually stop_times[0].sequence == 1
         // (sequence starts at 1)
-        let dep = stop_times.iter().find(|st| st.sequence == 1).unwrap().departure;
-        let path = sequence_hash(ty, &stop_times.iter()
+        let dep = match stop_times.iter().find(|st| st.sequence == 1) {
+            Some(st) => st.departure,
+            None => return Err(TripError::NoFirstStop { trip: id }),
+        };
+        let sequence = stop_times.iter()
             .map(|st| {
                 let tt::StopTime { stop, arrival, departure, .. } = *st;
                 let arrival = arrival - dep;
@@ -155,11 +194,12 @@ impl Trip {
                 });
                 st.stop
             })
-            .collect::<Vec<u16>>());
+            .collect::<Vec<u16>>();
+        let path = sequence_uuid(ty, &sequence).to_string();
         // if departure if after midnight but before 4am we assume it's the next day.
         let dep = if dep < TimeDelta::hours(4) { dep + TimeDelta::days(1) } else { dep };
         let dep = TimeDelta::from(dep);
-        (Self { 
+        Ok((Self { 
             id,
             delay: delay.unwrap_or(0.) as i32,
             direction: Direction::from(direction), 
@@ -172,7 +212,7 @@ impl Trip {
             times: StopTimes(times),
             headsign,
             last_event,
-        }, dep)
+        }, dep, sequence))
     }
 }
 
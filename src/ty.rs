@@ -1,4 +1,8 @@
 use serde::{Serialize,Deserialize};
+use uuid::Uuid;
+
+/// Root namespace of all the UUIDv5 generated by bruss.
+const NAMESPACE: Uuid = Uuid::from_u128(0x6d2f_9a1c_4b7e_5e03_8c41_d5a0_27f3_b918);
 
 #[derive(Serialize,Deserialize,Debug)]
 pub enum Type {
@@ -9,6 +13,9 @@ pub enum Type {
     Path,
     Segment,
     Schedule,
+    Transfer,
+    Station,
+    StopRoutes,
 }
 
 pub enum Identification {
@@ -27,12 +34,21 @@ impl Type {
             Self::Route => "routes",
             Self::Segment => "segments",
             Self::Schedule => "schedules",
+            Self::Transfer => "transfers",
+            Self::Station => "stations",
+            Self::StopRoutes => "stop_routes",
         }
     }
 
+    /// Namespace of the UUIDv5 identifiers generated for this type.
+    /// It's derived from the collection name, so it never changes across versions.
+    pub fn namespace(&self) -> Uuid {
+        Uuid::new_v5(&NAMESPACE, self.collection().as_bytes())
+    }
+
     pub fn identify(&self) -> Identification {
         match self {
-            Self::Segment => Identification::FromTo,
+            Self::Segment | Self::Transfer => Identification::FromTo,
             Self::Schedule => Identification::IdDate,
             _ => Identification::Id
         }
@@ -0,0 +1,140 @@
+use std::collections::HashMap;
+
+use serde::Serialize;
+use tt::AreaType;
+
+use crate::{map::{common_subsequence, merge_ordered}, Dataset, Direction, Path};
+
+/// How a variant of a route relates to its main variant.
+#[derive(Serialize, Debug, Clone, Copy, PartialEq, Eq)]
+#[serde(rename_all = "snake_case")]
+pub enum VariantKind {
+    Main,
+    /// Starts like the main variant and ends before it, e.g. a short turn.
+    Prefix,
+    /// Starts after the main variant and ends like it.
+    Suffix,
+    /// A part of the main variant, without its first and last stops.
+    Section,
+    /// Follows the main variant for a part, then leaves it at one or both ends.
+    Branch,
+    /// Leaves the main variant and joins it again, or skips some of its stops.
+    Detour,
+    /// Has no stops in common with the main variant.
+    Disjoint,
+}
+
+impl VariantKind {
+    /// The kind of the `variant` sequence of stops, relative to the `main` one. They're compared
+    /// on their longest common subsequence.
+    pub fn of(main: &[u16], variant: &[u16]) -> Self {
+        let common = common_subsequence(main, variant);
+        let (Some(first), Some(last)) = (common.first(), common.last()) else {
+            return Self::Disjoint;
+        };
+        if last.0 - first.0 + 1 != common.len() || last.1 - first.1 + 1 != common.len() {
+            return Self::Detour;
+        }
+        if common.len() < variant.len() {
+            return Self::Branch;
+        }
+        match (first.0 == 0, last.0 + 1 == main.len()) {
+            (true, true) => Self::Main,
+            (true, false) => Self::Prefix,
+            (false, true) => Self::Suffix,
+            (false, false) => Self::Section,
+        }
+    }
+}
+
+/// A path followed by the trips of a route.
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct Variant {
+    pub path: String,
+    pub kind: VariantKind,
+    /// Number of trips following the path.
+    pub trips: usize,
+    pub sequence: Vec<u16>,
+}
+
+/// # RouteVariants
+/// The paths of a route in one direction, classified against the main one, which is the path
+/// followed by most trips (the longest one on ties).
+///
+/// `sequence` holds the stops of all the variants in one order consistent with each of them: it's
+/// the main variant with the stops of the others inserted where they fall, in the order of
+/// `variants` (unlike `Path::merge_sequences`, which starts from the longest path).
+#[derive(Serialize, Debug, Clone, PartialEq)]
+pub struct RouteVariants {
+    pub route: u16,
+    pub direction: Direction,
+    #[serde(rename = "type")]
+    pub ty: AreaType,
+    /// Sorted by number of trips, the main variant first.
+    pub variants: Vec<Variant>,
+    pub sequence: Vec<u16>,
+}
+
+impl RouteVariants {
+    /// Analyzes the `paths` of a route, each with the number of trips following it. Returns `None`
+    /// if there are no paths.
+    pub fn new<'a>(route: u16, direction: Direction, paths: impl IntoIterator<Item = (&'a Path, usize)>) -> Option<Self> {
+        let mut paths = paths.into_iter().collect::<Vec<_>>();
+        paths.sort_by(|a, b| b.1.cmp(&a.1)
+            .then_with(|| b.0.sequence.len().cmp(&a.0.sequence.len()))
+            .then_with(|| a.0.id.cmp(&b.0.id)));
+        let (main, _) = paths.first()?;
+        let ty = main.ty;
+        let variants = paths.iter()
+            .map(|(p, trips)| Variant {
+                path: p.id.clone(),
+                kind: VariantKind::of(&main.sequence, &p.sequence),
+                trips: *trips,
+                sequence: p.sequence.clone(),
+            })
+            .collect::<Vec<_>>();
+        let sequence = merge_ordered(variants.iter().map(|v| v.sequence.as_slice()));
+        Some(Self { route, direction, ty, variants, sequence })
+    }
+
+    /// Analyzes the paths of the trips of `route` in `direction`. Trips whose path is unknown are
+    /// ignored.
+    pub fn from_dataset(dataset: &Dataset, route: u16, direction: Direction) -> Option<Self> {
+        let mut trips: HashMap<&str, usize> = HashMap::new();
+        for t in dataset.trips_of_route(route).filter(|t| t.direction == direction) {
+            *trips.entry(&t.path).or_default() += 1;
+        }
+        let paths = trips.into_iter().filter_map(|(id, n)| Some((dataset.path(id)?, n)));
+        Self::new(route, direction, paths)
+    }
+
+    pub fn main(&self) -> &Variant {
+        &self.variants[0]
+    }
+}
+
+#[test]
+fn variant_test_classify() {
+    use crate::RoutingType;
+
+    let main = [1, 2, 3, 4, 5];
+    for (variant, kind) in [
+        (vec![1, 2, 3], VariantKind::Prefix),
+        (vec![3, 4, 5], VariantKind::Suffix),
+        (vec![2, 3, 4], VariantKind::Section),
+        (vec![1, 2, 3, 6], VariantKind::Branch),
+        (vec![9, 4, 5], VariantKind::Branch),
+        (vec![1, 2, 7, 4, 5], VariantKind::Detour),
+        (vec![1, 3, 5], VariantKind::Detour),
+        (vec![8, 9], VariantKind::Disjoint),
+    ] {
+        assert_eq!(VariantKind::of(&main, &variant), kind, "{:?}", variant);
+    }
+
+    let path = |sequence: &[u16]| Path::new(sequence.to_vec(), AreaType::U, RoutingType::Bus);
+    let (main, branch, detour) = (path(&main), path(&[1, 2, 3, 6]), path(&[1, 2, 7, 4, 5]));
+    let v = RouteVariants::new(5, Direction::Forward, [(&branch, 1), (&main, 3), (&detour, 1)]).unwrap();
+    assert_eq!(v.main().path, main.id);
+    assert_eq!(v.variants.iter().map(|v| v.kind).collect::<Vec<_>>(), vec![VariantKind::Main, VariantKind::Detour, VariantKind::Branch]);
+    assert_eq!(v.sequence, vec![1, 2, 3, 7, 4, 5, 6]);
+}
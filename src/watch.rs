@@ -0,0 +1,233 @@
+use futures::{future, Stream, StreamExt};
+use mongodb::{
+    bson::{doc, Document},
+    change_stream::event::{ChangeStreamEvent, OperationType, ResumeToken},
+    options::{ChangeStreamOptions, FullDocumentType},
+    Database,
+};
+use tt::AreaType;
+
+use crate::{Area, BrussType, Path, Route, Schedule, Segment, Station, Stop, StopRoutes, Transfer, Trip};
+
+/// # Watched
+/// A `BrussType` whose collection can be followed through a change stream.
+///
+/// The constants hold the (dotted) field paths used to filter the stream by route and by area;
+/// `ROUTE_FIELD` is `None` for types that aren't related to a single route.
+pub trait Watched: BrussType + Unpin + Send + Sync + 'static {
+    const ROUTE_FIELD: Option<&'static str>;
+    const AREA_FIELD: &'static str;
+}
+
+impl Watched for Trip {
+    const ROUTE_FIELD: Option<&'static str> = Some("route");
+    const AREA_FIELD: &'static str = "type";
+}
+
+impl Watched for Schedule {
+    const ROUTE_FIELD: Option<&'static str> = Some("hints.route");
+    const AREA_FIELD: &'static str = "hints.type";
+}
+
+impl Watched for Route {
+    const ROUTE_FIELD: Option<&'static str> = Some("id");
+    const AREA_FIELD: &'static str = "area_ty";
+}
+
+impl Watched for Stop {
+    const ROUTE_FIELD: Option<&'static str> = None;
+    const AREA_FIELD: &'static str = "type";
+}
+
+impl Watched for Path {
+    const ROUTE_FIELD: Option<&'static str> = None;
+    const AREA_FIELD: &'static str = "type";
+}
+
+impl Watched for Segment {
+    const ROUTE_FIELD: Option<&'static str> = None;
+    const AREA_FIELD: &'static str = "type";
+}
+
+impl Watched for Area {
+    const ROUTE_FIELD: Option<&'static str> = None;
+    const AREA_FIELD: &'static str = "type";
+}
+
+/// A station matches an area if any of its stops is in it.
+impl Watched for Station {
+    const ROUTE_FIELD: Option<&'static str> = None;
+    const AREA_FIELD: &'static str = "stops.type";
+}
+
+/// A stop matches a route if the route serves it.
+impl Watched for StopRoutes {
+    const ROUTE_FIELD: Option<&'static str> = Some("routes.route");
+    const AREA_FIELD: &'static str = "type";
+}
+
+/// Transfers between areas are found filtering by the area of the origin stop.
+impl Watched for Transfer {
+    const ROUTE_FIELD: Option<&'static str> = None;
+    const AREA_FIELD: &'static str = "from.type";
+}
+
+/// Field-level difference carried by an update event.
+#[derive(Debug, Clone, PartialEq)]
+pub struct Diff {
+    /// Fields that have been set, with their new value.
+    pub updated: Document,
+    /// Fields that have been unset.
+    pub removed: Vec<String>,
+}
+
+#[derive(Debug)]
+pub enum Event<T> {
+    Insert(T),
+    /// `document` is the state of the document after the update, looked up by the server: it can
+    /// be missing if the document has been deleted in the meantime.
+    Update { key: Document, diff: Diff, document: Option<T> },
+    Replace(T),
+    Delete { key: Document },
+    /// The stream has been invalidated (e.g. the collection has been dropped) and it won't yield
+    /// any other event.
+    Invalidate,
+}
+
+/// A typed event, along with the token needed to resume the stream right after it.
+#[derive(Debug)]
+pub struct Change<T> {
+    pub token: ResumeToken,
+    pub event: Event<T>,
+}
+
+impl<T> Change<T> {
+    fn from_event(value: ChangeStreamEvent<T>) -> Option<Self> {
+        let ChangeStreamEvent { id: token, operation_type, document_key, update_description, full_document, .. } = value;
+        let key = document_key.unwrap_or_default();
+        let event = match operation_type {
+            OperationType::Insert => Event::Insert(full_document?),
+            OperationType::Replace => Event::Replace(full_document?),
+            OperationType::Update => {
+                let diff = match update_description {
+                    Some(d) => Diff { updated: d.updated_fields, removed: d.removed_fields },
+                    None => Diff { updated: Document::new(), removed: Vec::new() },
+                };
+                Event::Update { key, diff, document: full_document }
+            }
+            OperationType::Delete => Event::Delete { key },
+            OperationType::Invalidate => Event::Invalidate,
+            // drop, rename and other collection-wide events are followed by an invalidate.
+            _ => return None,
+        };
+        Some(Self { token, event })
+    }
+}
+
+/// # Watch
+/// Builder for a typed change stream over the collection of a `BrussType`.
+///
+/// Filters only apply to events that carry the full document: deletions don't, so they are always
+/// yielded and it's up to the consumer to discard the ones it doesn't know about.
+#[derive(Default)]
+pub struct Watch {
+    resume: Option<ResumeToken>,
+    route: Option<u16>,
+    area: Option<AreaType>,
+    filter: Option<Document>,
+}
+
+impl Watch {
+    pub fn new() -> Self {
+        Self::default()
+    }
+
+    /// Resume the stream right after the event that yielded `token`.
+    pub fn resume_after(mut self, token: ResumeToken) -> Self {
+        self.resume = Some(token);
+        self
+    }
+
+    /// Only yield documents belonging to `route`. Ignored for types that don't have a route.
+    pub fn route(mut self, route: u16) -> Self {
+        self.route = Some(route);
+        self
+    }
+
+    pub fn area(mut self, area: AreaType) -> Self {
+        self.area = Some(area);
+        self
+    }
+
+    /// Additional `$match` condition on the raw change events.
+    pub fn filter(mut self, filter: Document) -> Self {
+        self.filter = Some(filter);
+        self
+    }
+
+    fn pipeline<T: Watched>(&self) -> mongodb::error::Result<Vec<Document>> {
+        let mut conditions = Vec::new();
+        if let (Some(route), Some(field)) = (self.route, T::ROUTE_FIELD) {
+            let mut d = Document::new();
+            d.insert(format!("fullDocument.{}", field), route as i32);
+            conditions.push(d);
+        }
+        if let Some(area) = self.area {
+            let mut d = Document::new();
+            d.insert(format!("fullDocument.{}", T::AREA_FIELD), mongodb::bson::to_bson(&area)?);
+            conditions.push(d);
+        }
+        let mut pipeline = Vec::new();
+        if !conditions.is_empty() {
+            pipeline.push(doc! { "$match": { "$or": [
+                { "operationType": { "$in": ["delete", "invalidate"] } },
+                { "$and": conditions },
+            ] } });
+        }
+        if let Some(filter) = &self.filter {
+            pipeline.push(doc! { "$match": filter.clone() });
+        }
+        Ok(pipeline)
+    }
+
+    pub async fn stream<T: Watched>(self, db: &Database) -> mongodb::error::Result<impl Stream<Item = mongodb::error::Result<Change<T>>>> {
+        let pipeline = self.pipeline::<T>()?;
+        let options = ChangeStreamOptions::builder()
+            .full_document(Some(FullDocumentType::UpdateLookup))
+            .resume_after(self.resume)
+            .build();
+        let stream = T::get_coll(db).watch(pipeline, options).await?;
+        Ok(stream.filter_map(|r| future::ready(match r {
+            Ok(e) => Change::from_event(e).map(Ok),
+            Err(e) => Some(Err(e)),
+        })))
+    }
+}
+
+#[test]
+fn watch_test_pipeline_unfiltered() {
+    assert!(Watch::new().pipeline::<Trip>().unwrap().is_empty());
+    // stops have no route: the route filter is ignored
+    assert!(Watch::new().route(5).pipeline::<Stop>().unwrap().is_empty());
+    let filter = doc! { "operationType": "insert" };
+    assert_eq!(Watch::new().filter(filter.clone()).pipeline::<Trip>().unwrap(), vec![doc! { "$match": filter }]);
+}
+
+#[test]
+fn watch_test_pipeline_route_area() {
+    let area = mongodb::bson::to_bson(&AreaType::U).unwrap();
+    let pipeline = Watch::new().route(5).area(AreaType::U).pipeline::<Schedule>().unwrap();
+    assert_eq!(pipeline, vec![doc! { "$match": { "$or": [
+        { "operationType": { "$in": ["delete", "invalidate"] } },
+        { "$and": [{ "fullDocument.hints.route": 5 }, { "fullDocument.hints.type": area.clone() }] },
+    ] } }]);
+
+    let pipeline = Watch::new().area(AreaType::U).filter(doc! { "fullDocument.bus_id": 12 }).pipeline::<Trip>().unwrap();
+    assert_eq!(pipeline, vec![
+        doc! { "$match": { "$or": [
+            { "operationType": { "$in": ["delete", "invalidate"] } },
+            { "$and": [{ "fullDocument.type": area }] },
+        ] } },
+        doc! { "$match": { "fullDocument.bus_id": 12 } },
+    ]);
+}